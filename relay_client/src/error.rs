@@ -11,6 +11,9 @@ pub enum RequestBuildError {
     #[error("Failed to add request headers")]
     Headers,
 
+    #[error("Failed to create auth token: {0}")]
+    AuthToken(#[from] relay_rpc::auth::Error),
+
     #[error("Failed to parse connection URL: {0}")]
     Url(#[from] url::ParseError),
 
@@ -53,4 +56,7 @@ pub enum Error {
 
     #[error("Invalid request type")]
     InvalidRequestType,
+
+    #[error("Request timed out")]
+    Timeout,
 }
@@ -1,6 +1,7 @@
 use {
     crate::{
         error::{BoxError, Error},
+        tls::TlsError,
         ConnectionOptions,
         MessageIdGenerator,
     },
@@ -30,6 +31,9 @@ pub enum HttpClientError {
     #[error("HTTP transport error: {0}")]
     Transport(#[from] TransportError),
 
+    #[error("TLS error: {0}")]
+    Tls(#[from] TlsError),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(BoxError),
 
@@ -86,10 +90,34 @@ impl Client {
         let mut headers = HeaderMap::new();
         opts.update_request_headers(&mut headers)?;
 
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(HttpClientError::Transport)?;
+        let builder = reqwest::Client::builder().default_headers(headers);
+
+        let builder = match &opts.proxy {
+            Some(proxy) => builder.proxy(
+                proxy
+                    .as_reqwest_proxy()
+                    .map_err(HttpClientError::Transport)?,
+            ),
+
+            // Otherwise `reqwest` would pick up the proxy from the environment.
+            None => builder.no_proxy(),
+        };
+
+        let builder = match &opts.tls {
+            // `reqwest` doesn't expose the connection, so the pins could only be
+            // verified once the request has been sent.
+            Some(tls) if !tls.spki_pins.is_empty() => {
+                return Err(HttpClientError::Tls(TlsError::PinningUnsupported).into());
+            }
+
+            Some(tls) => tls
+                .configure_reqwest(builder)
+                .map_err(HttpClientError::Transport)?,
+
+            None => builder,
+        };
+
+        let client = builder.build().map_err(HttpClientError::Transport)?;
 
         let url = opts.as_url()?;
         let origin = url.origin().unicode_serialization();
@@ -1,25 +1,45 @@
 use {
-    crate::error::{Error, RequestBuildError},
+    crate::{
+        error::{Error, RequestBuildError},
+        proxy::Proxy,
+        tls::TlsConfig,
+        websocket::{
+            AckMode,
+            KeepaliveConfig,
+            OfflineQueueConfig,
+            ReconnectPolicy,
+            WebsocketConfig,
+        },
+    },
     ::http::HeaderMap,
+    once_cell::sync::Lazy,
     relay_rpc::{
-        auth::{SerializedAuthToken, RELAY_WEBSOCKET_ADDRESS},
+        auth::{identity::Identity, AuthToken, SerializedAuthToken, RELAY_WEBSOCKET_ADDRESS},
         domain::{MessageId, ProjectId},
         user_agent::UserAgent,
     },
     serde::Serialize,
-    std::sync::{
-        atomic::{AtomicU8, Ordering},
-        Arc,
+    std::{
+        sync::{
+            atomic::{AtomicU8, Ordering},
+            Arc,
+        },
+        time::Duration,
     },
     url::Url,
 };
 
 pub mod error;
 pub mod http;
+pub mod proxy;
+pub mod tls;
 pub mod websocket;
 
 pub type HttpRequest<T> = ::http::Request<T>;
 
+/// User agent of this SDK, attached by [`ConnectionOptions::new()`].
+static DEFAULT_USER_AGENT: Lazy<UserAgent> = Lazy::new(|| UserAgent::rust_sdk().build());
+
 /// Relay authorization method. A wrapper around [`SerializedAuthToken`].
 #[derive(Debug, Clone)]
 pub enum Authorization {
@@ -30,6 +50,19 @@ pub enum Authorization {
     Header(SerializedAuthToken),
 }
 
+/// Hook issuing a fresh auth token for each automatic reconnection attempt.
+/// See [`ConnectionOptions::with_auth_refresh()`].
+#[derive(Clone)]
+pub struct AuthRefresh(
+    Arc<dyn Fn() -> Result<SerializedAuthToken, relay_rpc::auth::Error> + Send + Sync>,
+);
+
+impl std::fmt::Debug for AuthRefresh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthRefresh").finish_non_exhaustive()
+    }
+}
+
 /// Relay connection options.
 #[derive(Debug, Clone)]
 pub struct ConnectionOptions {
@@ -44,29 +77,109 @@ pub struct ConnectionOptions {
     /// The authorization method and auth token to use.
     pub auth: Authorization,
 
+    /// Optional hook replacing the auth token before each automatic
+    /// reconnection attempt, since the original one may have expired by then.
+    /// Without it, the client stops reconnecting once the Relay rejects the
+    /// token. Disabled by default.
+    pub auth_refresh: Option<AuthRefresh>,
+
     /// Optional origin of the request. Subject to allow-list validation.
     pub origin: Option<String>,
 
     /// Optional user agent parameters.
     pub user_agent: Option<UserAgent>,
+
+    /// Optional proxy for both the websocket and the HTTP clients. Defaults to
+    /// [`Proxy::from_env()`].
+    pub proxy: Option<Proxy>,
+
+    /// Optional TLS configuration for both the websocket and the HTTP clients,
+    /// e.g. to trust a private CA or pin the Relay certificates. Disabled by
+    /// default.
+    pub tls: Option<TlsConfig>,
+
+    /// Optional policy for reconnecting the websocket client automatically.
+    /// Disabled by default.
+    pub reconnect_policy: Option<ReconnectPolicy>,
+
+    /// Optional keepalive pings for the websocket client. Disabled by default.
+    pub keepalive: Option<KeepaliveConfig>,
+
+    /// Optional default timeout for the websocket client requests. Can be
+    /// overridden per request with
+    /// [`ResponseFuture::with_timeout()`](websocket::ResponseFuture::with_timeout).
+    /// Disabled by default.
+    pub request_timeout: Option<Duration>,
+
+    /// Optional queue buffering the websocket client requests while
+    /// disconnected, which are sent in order once the connection is
+    /// re-established. Disabled by default.
+    pub offline_queue: Option<OfflineQueueConfig>,
+
+    /// How the websocket client acknowledges the received messages. Defaults
+    /// to [`AckMode::Auto`].
+    pub ack_mode: AckMode,
+
+    /// Websocket protocol limits and socket options. See [`WebsocketConfig`]
+    /// for the defaults.
+    pub websocket: WebsocketConfig,
 }
 
 impl ConnectionOptions {
+    /// Creates connection options with the user agent of this SDK attached.
+    /// Use [`ConnectionOptions::with_user_agent()`] to replace it, or pass
+    /// `None` to opt out.
     pub fn new(project_id: impl Into<ProjectId>, auth: SerializedAuthToken) -> Self {
         Self {
             address: RELAY_WEBSOCKET_ADDRESS.into(),
             project_id: project_id.into(),
             auth: Authorization::Query(auth),
+            auth_refresh: None,
             origin: None,
-            user_agent: None,
+            user_agent: Some(DEFAULT_USER_AGENT.clone()),
+            proxy: Proxy::from_env(),
+            tls: None,
+            reconnect_policy: None,
+            keepalive: None,
+            request_timeout: None,
+            offline_queue: None,
+            ack_mode: AckMode::Auto,
+            websocket: WebsocketConfig::default(),
         }
     }
 
+    /// Creates connection options with the auth token signed by the provided
+    /// [`Identity`] key.
+    pub fn from_identity(
+        project_id: impl Into<ProjectId>,
+        identity: &Identity,
+        auth: &AuthToken,
+    ) -> Result<Self, relay_rpc::auth::Error> {
+        let auth = auth.as_jwt(identity.keypair())?;
+
+        Ok(Self::new(project_id, auth))
+    }
+
     pub fn with_address(mut self, address: impl Into<String>) -> Self {
         self.address = address.into();
         self
     }
 
+    /// Sets the hook issuing a fresh auth token for each automatic
+    /// reconnection attempt, e.g. signing a new [`AuthToken`] with the client
+    /// [`Identity`]. The token is passed with the same [`Authorization`]
+    /// method as the original one.
+    pub fn with_auth_refresh(
+        mut self,
+        refresh: impl Fn() -> Result<SerializedAuthToken, relay_rpc::auth::Error>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.auth_refresh = Some(AuthRefresh(Arc::new(refresh)));
+        self
+    }
+
     pub fn with_origin(mut self, origin: impl Into<Option<String>>) -> Self {
         self.origin = origin.into();
         self
@@ -77,6 +190,49 @@ impl ConnectionOptions {
         self
     }
 
+    pub fn with_proxy(mut self, proxy: impl Into<Option<Proxy>>) -> Self {
+        self.proxy = proxy.into();
+        self
+    }
+
+    pub fn with_tls(mut self, tls: impl Into<Option<TlsConfig>>) -> Self {
+        self.tls = tls.into();
+        self
+    }
+
+    pub fn with_reconnect_policy(
+        mut self,
+        reconnect_policy: impl Into<Option<ReconnectPolicy>>,
+    ) -> Self {
+        self.reconnect_policy = reconnect_policy.into();
+        self
+    }
+
+    pub fn with_keepalive(mut self, keepalive: impl Into<Option<KeepaliveConfig>>) -> Self {
+        self.keepalive = keepalive.into();
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.request_timeout = timeout.into();
+        self
+    }
+
+    pub fn with_offline_queue(mut self, queue: impl Into<Option<OfflineQueueConfig>>) -> Self {
+        self.offline_queue = queue.into();
+        self
+    }
+
+    pub fn with_ack_mode(mut self, ack_mode: AckMode) -> Self {
+        self.ack_mode = ack_mode;
+        self
+    }
+
+    pub fn with_websocket(mut self, websocket: WebsocketConfig) -> Self {
+        self.websocket = websocket;
+        self
+    }
+
     pub fn as_url(&self) -> Result<Url, RequestBuildError> {
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
@@ -103,6 +259,22 @@ impl ConnectionOptions {
         Ok(url)
     }
 
+    /// Replaces the auth token using the [`AuthRefresh`] hook, if any.
+    fn refresh_auth(&mut self) -> Result<(), relay_rpc::auth::Error> {
+        let Some(refresh) = &self.auth_refresh else {
+            return Ok(());
+        };
+
+        let token = (refresh.0)()?;
+
+        self.auth = match self.auth {
+            Authorization::Query(_) => Authorization::Query(token),
+            Authorization::Header(_) => Authorization::Header(token),
+        };
+
+        Ok(())
+    }
+
     fn as_ws_request(&self) -> Result<HttpRequest<()>, RequestBuildError> {
         use {
             crate::websocket::WebsocketClientError,
@@ -0,0 +1,497 @@
+use {
+    data_encoding::BASE64,
+    sha2::{Digest, Sha256},
+    tokio_tungstenite::Connector,
+};
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+use {
+    rustls::{
+        client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+        ClientConfig,
+        RootCertStore,
+        ServerName,
+    },
+    std::{sync::Arc, time::SystemTime},
+};
+#[cfg(feature = "native-tls")]
+use {tokio::net::TcpStream, tokio_tungstenite::MaybeTlsStream};
+
+/// Errors generated while configuring TLS or establishing a TLS connection.
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("Invalid certificate")]
+    Certificate,
+
+    #[error("Invalid client identity")]
+    Identity,
+
+    #[cfg(feature = "native-tls")]
+    #[error("TLS error: {0}")]
+    Tls(#[from] native_tls::Error),
+
+    #[cfg(feature = "rustls")]
+    #[error("Failed to load the system root certificates: {0}")]
+    SystemRoots(std::io::Error),
+
+    #[error("Server certificate doesn't match the pinned public keys")]
+    PinMismatch,
+
+    /// The HTTP client can't verify the pins before sending the requests.
+    #[error("Certificate pinning is not supported by the HTTP client")]
+    PinningUnsupported,
+}
+
+/// DER-encoded X.509 certificate.
+#[derive(Debug, Clone)]
+pub struct Certificate(Vec<u8>);
+
+impl Certificate {
+    pub fn from_pem(pem: &[u8]) -> Result<Self, TlsError> {
+        let der = pem_blocks(pem, "CERTIFICATE")
+            .and_then(|blocks| blocks.into_iter().next())
+            .ok_or(TlsError::Certificate)?;
+
+        Self::from_der(&der)
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, TlsError> {
+        match der_element(der) {
+            Some((cert, _, rest)) if rest.is_empty() && spki_sha256(cert).is_some() => {
+                Ok(Self(der.to_vec()))
+            }
+
+            _ => Err(TlsError::Certificate),
+        }
+    }
+
+    pub fn as_der(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the SHA-256 hash of the certificate's `SubjectPublicKeyInfo`,
+    /// for use as a pin in [`TlsConfig::with_spki_pin()`].
+    pub fn spki_sha256(&self) -> Option<[u8; 32]> {
+        spki_sha256(&self.0)
+    }
+}
+
+/// Client certificate and its private key, for the mutual TLS authentication.
+#[derive(Clone)]
+#[cfg_attr(not(any(feature = "native-tls", feature = "rustls")), allow(dead_code))]
+pub struct ClientIdentity {
+    cert: Vec<u8>,
+    key: Vec<u8>,
+}
+
+impl ClientIdentity {
+    /// Creates the identity from the PEM-encoded certificate chain and the
+    /// PEM-encoded PKCS #8 private key.
+    pub fn from_pkcs8_pem(cert: &[u8], key: &[u8]) -> Result<Self, TlsError> {
+        let certs = pem_blocks(cert, "CERTIFICATE").ok_or(TlsError::Identity)?;
+        let keys = pem_blocks(key, "PRIVATE KEY").ok_or(TlsError::Identity)?;
+
+        if certs.is_empty() || keys.len() != 1 {
+            return Err(TlsError::Identity);
+        }
+
+        Ok(Self {
+            cert: cert.to_vec(),
+            key: key.to_vec(),
+        })
+    }
+}
+
+impl std::fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientIdentity").finish_non_exhaustive()
+    }
+}
+
+/// TLS configuration for both the websocket and the HTTP clients (see
+/// [`ConnectionOptions::tls`](crate::ConnectionOptions::tls)).
+///
+/// Applies to the TLS backend selected by the crate features: the platform TLS
+/// implementation with the default `native-tls` feature, or `rustls` with the
+/// `rustls` feature only.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// Additional trusted root certificates, e.g. the private CA of a
+    /// self-hosted Relay.
+    pub root_certificates: Vec<Certificate>,
+
+    /// Whether to trust the system root certificates. Enabled by default.
+    pub built_in_roots: bool,
+
+    /// Optional client certificate.
+    pub identity: Option<ClientIdentity>,
+
+    /// SHA-256 hashes of the `SubjectPublicKeyInfo` of the accepted server
+    /// certificates (see [`Certificate::spki_sha256()`]). If not empty, the
+    /// connections to servers presenting a certificate with a different public
+    /// key fail with [`TlsError::PinMismatch`].
+    ///
+    /// The websocket client verifies the pins right after the TLS handshake,
+    /// before sending the websocket upgrade request. The HTTP client can't
+    /// verify them before sending the requests, so it fails to build with
+    /// [`TlsError::PinningUnsupported`] if there are any.
+    pub spki_pins: Vec<[u8; 32]>,
+
+    /// Disables the validation of the server certificates and host names,
+    /// although the pins are still verified.
+    ///
+    /// **This is insecure, and intended for tests only.**
+    pub insecure: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            root_certificates: Vec::new(),
+            built_in_roots: true,
+            identity: None,
+            spki_pins: Vec::new(),
+            insecure: false,
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn with_root_certificate(mut self, cert: Certificate) -> Self {
+        self.root_certificates.push(cert);
+        self
+    }
+
+    pub fn with_built_in_roots(mut self, built_in_roots: bool) -> Self {
+        self.built_in_roots = built_in_roots;
+        self
+    }
+
+    pub fn with_identity(mut self, identity: impl Into<Option<ClientIdentity>>) -> Self {
+        self.identity = identity.into();
+        self
+    }
+
+    pub fn with_spki_pin(mut self, pin: [u8; 32]) -> Self {
+        self.spki_pins.push(pin);
+        self
+    }
+
+    pub fn with_insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+
+    /// Returns the `tokio-tungstenite` connector of the enabled TLS backend,
+    /// e.g. for a custom [`Connector`](crate::websocket::Connector).
+    ///
+    /// The `native-tls` connector doesn't verify the pins, as the platform TLS
+    /// implementation can't be made to fail the handshake on a mismatch.
+    #[cfg(feature = "native-tls")]
+    pub fn connector(&self) -> Result<Connector, TlsError> {
+        Ok(Connector::NativeTls(self.native_connector()?))
+    }
+
+    #[cfg(feature = "native-tls")]
+    fn native_connector(&self) -> Result<native_tls::TlsConnector, TlsError> {
+        let mut builder = native_tls::TlsConnector::builder();
+
+        builder
+            .disable_built_in_roots(!self.built_in_roots)
+            .danger_accept_invalid_certs(self.insecure)
+            .danger_accept_invalid_hostnames(self.insecure);
+
+        for cert in &self.root_certificates {
+            builder.add_root_certificate(native_tls::Certificate::from_der(&cert.0)?);
+        }
+
+        if let Some(identity) = &self.identity {
+            builder.identity(
+                native_tls::Identity::from_pkcs8(&identity.cert, &identity.key)
+                    .map_err(|_| TlsError::Identity)?,
+            );
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Returns the `tokio-tungstenite` connector of the enabled TLS backend,
+    /// e.g. for a custom [`Connector`](crate::websocket::Connector).
+    #[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+    pub fn connector(&self) -> Result<Connector, TlsError> {
+        let mut roots = RootCertStore::empty();
+
+        if self.built_in_roots {
+            let certs = rustls_native_certs::load_native_certs().map_err(TlsError::SystemRoots)?;
+            let certs = certs.into_iter().map(|cert| cert.0).collect::<Vec<_>>();
+            roots.add_parsable_certificates(&certs);
+        }
+
+        for cert in &self.root_certificates {
+            roots
+                .add(&rustls::Certificate(cert.0.clone()))
+                .map_err(|_| TlsError::Certificate)?;
+        }
+
+        let verifier = PinningVerifier {
+            webpki: (!self.insecure).then(|| WebPkiVerifier::new(roots, None)),
+            spki_pins: self.spki_pins.clone(),
+        };
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+
+        let config = match &self.identity {
+            Some(identity) => {
+                let certs = pem_blocks(&identity.cert, "CERTIFICATE").ok_or(TlsError::Identity)?;
+                let key = pem_blocks(&identity.key, "PRIVATE KEY")
+                    .and_then(|keys| keys.into_iter().next())
+                    .ok_or(TlsError::Identity)?;
+
+                builder
+                    .with_single_cert(
+                        certs.into_iter().map(rustls::Certificate).collect(),
+                        rustls::PrivateKey(key),
+                    )
+                    .map_err(|_| TlsError::Identity)?
+            }
+
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(Connector::Rustls(Arc::new(config)))
+    }
+
+    /// Returns the `tokio-tungstenite` connector of the enabled TLS backend,
+    /// e.g. for a custom [`Connector`](crate::websocket::Connector).
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    pub fn connector(&self) -> Result<Connector, TlsError> {
+        // Fails the `wss` connections, as there's no TLS backend.
+        Ok(Connector::Plain)
+    }
+
+    /// Establishes the TLS connection over the stream, with the enabled TLS
+    /// backend. Fails if the server certificate doesn't match the pins, before
+    /// anything is sent over the connection.
+    #[cfg(feature = "native-tls")]
+    pub(crate) async fn connect(
+        &self,
+        domain: &str,
+        stream: TcpStream,
+    ) -> Result<MaybeTlsStream<TcpStream>, TlsError> {
+        let connector = tokio_native_tls::TlsConnector::from(self.native_connector()?);
+        let stream = connector.connect(domain, stream).await?;
+
+        let cert = stream.get_ref().peer_certificate()?;
+        let der = cert.map(|cert| cert.to_der()).transpose()?;
+        verify_pins(&self.spki_pins, der.as_deref())?;
+
+        Ok(MaybeTlsStream::NativeTls(stream))
+    }
+
+    /// Applies the configuration to the HTTP client.
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    pub(crate) fn configure_reqwest(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> reqwest::Result<reqwest::ClientBuilder> {
+        #[cfg(feature = "native-tls")]
+        let builder = builder
+            .use_native_tls()
+            .danger_accept_invalid_hostnames(self.insecure);
+
+        #[cfg(not(feature = "native-tls"))]
+        let builder = builder.use_rustls_tls();
+
+        let mut builder = builder
+            .tls_built_in_root_certs(self.built_in_roots)
+            .danger_accept_invalid_certs(self.insecure);
+
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_der(&cert.0)?);
+        }
+
+        if let Some(identity) = &self.identity {
+            #[cfg(feature = "native-tls")]
+            let identity = reqwest::Identity::from_pkcs8_pem(&identity.cert, &identity.key)?;
+
+            #[cfg(not(feature = "native-tls"))]
+            let identity =
+                reqwest::Identity::from_pem(&[&identity.cert[..], &identity.key].concat())?;
+
+            builder = builder.identity(identity);
+        }
+
+        Ok(builder)
+    }
+
+    /// Applies the configuration to the HTTP client.
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    pub(crate) fn configure_reqwest(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> reqwest::Result<reqwest::ClientBuilder> {
+        Ok(builder)
+    }
+}
+
+/// Verifies the server certificates with `webpki`, unless the configuration is
+/// insecure, and then against the pins.
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+struct PinningVerifier {
+    webpki: Option<WebPkiVerifier>,
+    spki_pins: Vec<[u8; 32]>,
+}
+
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        verify_pins(&self.spki_pins, Some(&end_entity.0))
+            .map_err(|err| rustls::Error::General(err.to_string()))?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg_attr(not(any(feature = "native-tls", feature = "rustls")), allow(dead_code))]
+fn verify_pins(pins: &[[u8; 32]], cert: Option<&[u8]>) -> Result<(), TlsError> {
+    if pins.is_empty() {
+        return Ok(());
+    }
+
+    match cert.and_then(spki_sha256) {
+        Some(hash) if pins.contains(&hash) => Ok(()),
+        _ => Err(TlsError::PinMismatch),
+    }
+}
+
+/// Returns the decoded contents of the PEM blocks with the label, or `None` if
+/// any of them is malformed.
+fn pem_blocks(pem: &[u8], label: &str) -> Option<Vec<Vec<u8>>> {
+    let begin = format!("-----BEGIN {label}-----");
+    let end = format!("-----END {label}-----");
+
+    let mut pem = std::str::from_utf8(pem).ok()?;
+    let mut blocks = Vec::new();
+
+    while let Some(start) = pem.find(&begin) {
+        let (contents, rest) = pem[start + begin.len()..].split_once(&end)?;
+        let contents = contents
+            .chars()
+            .filter(|c| !c.is_ascii_whitespace())
+            .collect::<String>();
+
+        blocks.push(BASE64.decode(contents.as_bytes()).ok()?);
+        pem = rest;
+    }
+
+    Some(blocks)
+}
+
+/// Returns the SHA-256 hash of the `SubjectPublicKeyInfo` of the DER-encoded
+/// X.509 certificate.
+fn spki_sha256(cert: &[u8]) -> Option<[u8; 32]> {
+    // Certificate ::= SEQUENCE { tbsCertificate, ... }
+    let (_, cert, _) = der_element(cert)?;
+    let (_, mut fields, _) = der_element(cert)?;
+
+    // TBSCertificate ::= SEQUENCE { [0] version OPTIONAL, serialNumber, signature,
+    // issuer, validity, subject, subjectPublicKeyInfo, ... }
+    if fields.first() == Some(&0xa0) {
+        fields = der_element(fields)?.2;
+    }
+
+    for _ in 0..5 {
+        fields = der_element(fields)?.2;
+    }
+
+    let (spki, _, _) = der_element(fields)?;
+
+    Some(Sha256::digest(spki).into())
+}
+
+/// Splits the DER element at the start of the data, returning the whole
+/// element, its contents, and the rest of the data.
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let len_byte = *data.get(1)?;
+
+    let (header_len, len) = if len_byte < 0x80 {
+        (2, len_byte as usize)
+    } else {
+        let len_bytes = (len_byte & 0x7f) as usize;
+
+        if len_bytes == 0 || len_bytes > std::mem::size_of::<usize>() {
+            return None;
+        }
+
+        let len = data
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0, |len, byte| len << 8 | *byte as usize);
+
+        (2 + len_bytes, len)
+    };
+
+    let end = header_len.checked_add(len)?;
+    let element = data.get(..end)?;
+
+    Some((element, &element[header_len..], &data[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CERT: &[u8] = b"-----BEGIN CERTIFICATE-----
+MIIBfzCCASWgAwIBAgIUG5ZJ0xPKyaZ7IsalaqKFi8F/6TowCgYIKoZIzj0EAwIw
+FDESMBAGA1UEAwwJbG9jYWxob3N0MCAXDTI2MTAxOTAzMTc0N1oYDzIxMjYwOTI1
+MDMxNzQ3WjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcqhkjOPQIBBggqhkjO
+PQMBBwNCAATRMz7mTe3ULirHgvcLKF8V5wKzGZVho2J4eVj4VYTQaytRNtxIedKo
+p031KzqlxntCW2ZNmIzLpiTVtvbzHq6oo1MwUTAdBgNVHQ4EFgQUZ5/Rd3/0oGmq
+eWndoWbIHwkKBCQwHwYDVR0jBBgwFoAUZ5/Rd3/0oGmqeWndoWbIHwkKBCQwDwYD
+VR0TAQH/BAUwAwEB/zAKBggqhkjOPQQDAgNIADBFAiEA3Sa3rNQcwYVhofBBrT+E
+feIEUO5HpoFVHDOVElUSSYMCICgwBPAs7Ofgh3tAvnNPXea4zNhIMSSEQrGtHzLe
+auQN
+-----END CERTIFICATE-----
+";
+
+    const SPKI_SHA256: &str = "550330c4bf4ae39696df8c2e2b63b3787753d3d9e6586a50cf82dac65442003c";
+
+    #[test]
+    fn spki_pin() {
+        let cert = Certificate::from_pem(CERT).unwrap();
+        let pin = cert.spki_sha256().unwrap();
+
+        assert_eq!(data_encoding::HEXLOWER.encode(&pin), SPKI_SHA256);
+
+        assert!(verify_pins(&[pin], Some(cert.as_der())).is_ok());
+        assert!(verify_pins(&[pin], None).is_err());
+        assert!(verify_pins(&[], None).is_ok());
+
+        assert!(matches!(
+            verify_pins(&[[0; 32]], Some(cert.as_der())),
+            Err(TlsError::PinMismatch)
+        ));
+
+        assert!(spki_sha256(&cert.as_der()[..100]).is_none());
+    }
+}
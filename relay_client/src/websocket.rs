@@ -1,6 +1,19 @@
 use {
-    self::connection::{connection_event_loop, ConnectionControl},
-    crate::{error::Error, ConnectionOptions},
+    self::{
+        backpressure::QueueGauges,
+        connection::{connection_event_loop, Connection, ConnectionControl},
+        events::EventSink,
+        subscription_stream::MessageRoutes,
+        subscriptions::SubscriptionRegistry,
+    },
+    crate::{
+        error::Error,
+        proxy::ProxyError,
+        tls::TlsError,
+        ConnectionOptions,
+        MessageIdGenerator,
+    },
+    futures_util::FutureExt,
     relay_rpc::{
         domain::{MessageId, SubscriptionId, Topic},
         rpc::{
@@ -11,23 +24,44 @@ use {
             FetchMessages,
             Publish,
             Receipt,
+            RequestPayload,
             Subscribe,
             Subscription,
             Unsubscribe,
         },
     },
-    std::{sync::Arc, time::Duration},
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    },
     tokio::sync::{
-        mpsc::{self, UnboundedSender},
+        mpsc::{self, error::TrySendError, Sender, UnboundedSender},
         oneshot,
+        watch,
     },
 };
 pub use {
+    ack::*,
+    backpressure::*,
+    close_code::*,
+    config::*,
+    events::*,
     fetch::*,
     inbound::*,
+    keepalive::*,
     outbound::*,
+    queue::*,
+    reconnect::*,
+    retry::*,
+    state::*,
     stream::*,
-    tokio_tungstenite::tungstenite::protocol::CloseFrame,
+    subscription_stream::*,
+    tokio_tungstenite::tungstenite::{protocol::CloseFrame, Message},
+    transport::*,
 };
 
 pub type TransportError = tokio_tungstenite::tungstenite::Error;
@@ -37,6 +71,18 @@ pub enum WebsocketClientError {
     #[error("Failed to connect: {0}")]
     ConnectionFailed(TransportError),
 
+    #[error("Failed to connect through proxy: {0}")]
+    Proxy(#[from] ProxyError),
+
+    #[error("TLS error: {0}")]
+    Tls(#[from] TlsError),
+
+    #[error("Connection timed out")]
+    ConnectTimeout,
+
+    #[error("Inbound message of {size} bytes exceeds the limit of {max_size} bytes")]
+    MessageTooLarge { size: usize, max_size: usize },
+
     #[error("Connection closed: {0}")]
     ConnectionClosed(CloseReason),
 
@@ -48,6 +94,15 @@ pub enum WebsocketClientError {
 
     #[error("Not connected")]
     NotConnected,
+
+    #[error("Offline queue is full")]
+    OfflineQueueFull,
+
+    #[error("Queue is full")]
+    QueueFull,
+
+    #[error("Request expired in the offline queue")]
+    OfflineQueueExpired,
 }
 
 /// Wrapper around the websocket [`CloseFrame`] providing info about the
@@ -55,6 +110,13 @@ pub enum WebsocketClientError {
 #[derive(Debug, Clone)]
 pub struct CloseReason(pub Option<CloseFrame<'static>>);
 
+impl CloseReason {
+    /// Returns the decoded close code, if the close frame is available.
+    pub fn code(&self) -> Option<RelayCloseCode> {
+        self.0.as_ref().map(RelayCloseCode::from)
+    }
+}
+
 impl std::fmt::Display for CloseReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if let Some(frame) = &self.0 {
@@ -65,14 +127,27 @@ impl std::fmt::Display for CloseReason {
     }
 }
 
+mod ack;
+mod backpressure;
+mod close_code;
+mod config;
 mod connection;
+mod events;
 mod fetch;
 mod inbound;
+mod keepalive;
 mod outbound;
+mod queue;
+mod reconnect;
+mod retry;
+mod state;
 mod stream;
+mod subscription_stream;
+mod subscriptions;
+mod transport;
 
 /// The message received from a subscription.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct PublishedMessage {
     pub message_id: MessageId,
     pub subscription_id: SubscriptionId,
@@ -81,6 +156,9 @@ pub struct PublishedMessage {
     pub tag: u32,
     pub published_at: chrono::DateTime<chrono::Utc>,
     pub received_at: chrono::DateTime<chrono::Utc>,
+
+    /// Token for acknowledging the message in the [`AckMode::Manual`] mode.
+    pub ack: Option<MessageAck>,
 }
 
 impl PublishedMessage {
@@ -97,6 +175,7 @@ impl PublishedMessage {
             // TODO: Set proper value once implemented.
             published_at: now,
             received_at: now,
+            ack: None,
         }
     }
 }
@@ -119,15 +198,47 @@ pub trait ConnectionHandler: Send + 'static {
     /// Called when an outbound error occurs, i.e. failed to write to the
     /// websocket stream.
     fn outbound_error(&mut self, _error: Error) {}
+
+    /// Called when a pong is received in response to a keepalive ping (see
+    /// [`KeepaliveConfig`]), with the round-trip time.
+    fn pong_received(&mut self, _latency: Duration) {}
+
+    /// Called before an automatic reconnection attempt (see
+    /// [`ReconnectPolicy`]), with the attempt number starting at `1` and the
+    /// delay before the attempt.
+    fn reconnecting(&mut self, _attempt: u32, _delay: Duration) {}
+
+    /// Called when the connection is re-established automatically, after
+    /// [`ConnectionHandler::connected()`].
+    fn reconnected(&mut self, _attempts: u32) {}
+
+    /// Called when the automatic reconnection gives up, with the error of the
+    /// last attempt.
+    fn reconnect_failed(&mut self, _error: Error) {}
+
+    /// Called when the subscriptions to the topics could not be restored after
+    /// the connection was re-established.
+    fn resubscribe_failed(&mut self, _topics: Vec<Topic>, _error: Error) {}
 }
 
 /// The Relay WebSocket RPC client.
 ///
 /// This provides the high-level access to all of the available RPC methods. For
-/// a lower-level RPC stream see [`ClientStream`](crate::client::ClientStream).
+/// a lower-level RPC stream see [`ClientStream`].
 #[derive(Debug, Clone)]
 pub struct Client {
     control_tx: UnboundedSender<ConnectionControl>,
+    request_tx: Sender<OutboundRequest>,
+    overflow: OverflowPolicy,
+    gauges: QueueGauges,
+    state: watch::Receiver<ConnectionState>,
+    subscriptions: SubscriptionRegistry,
+    routes: MessageRoutes,
+    id_generator: MessageIdGenerator,
+
+    /// Default request timeout in milliseconds taken from the
+    /// [`ConnectionOptions`], or zero if there's none.
+    request_timeout: Arc<AtomicU64>,
 }
 
 impl Client {
@@ -136,11 +247,61 @@ impl Client {
     where
         T: ConnectionHandler,
     {
-        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        Self::builder().build(handler)
+    }
 
-        tokio::spawn(connection_event_loop(control_rx, handler));
+    /// Creates a new [`Client`] delivering the events to the returned
+    /// [`ClientEvents`] stream over the channel, instead of a
+    /// [`ConnectionHandler`].
+    pub fn with_events(channel: EventChannel) -> (Self, ClientEvents) {
+        Self::builder().build_with_events(channel)
+    }
 
-        Self { control_tx }
+    /// Returns a [`ClientBuilder`] for creating a [`Client`] with a custom
+    /// configuration.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    fn with_event_sink(events: EventSink, builder: ClientBuilder) -> Self {
+        let ClientBuilder { queues, connector } = builder;
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        let (request_tx, request_rx) = mpsc::channel(queues.control_capacity());
+        let overflow = queues.overflow;
+        let gauges = QueueGauges::new(queues.control_capacity());
+        let (state_tx, state) = watch::channel(ConnectionState::Disconnected);
+        let subscriptions = SubscriptionRegistry::default();
+        let routes = MessageRoutes::new(queues.stream_capacity());
+        let id_generator = MessageIdGenerator::new();
+
+        let conn = Connection::new(
+            subscriptions.clone(),
+            queues,
+            gauges.clone(),
+            state_tx,
+            connector,
+            id_generator.clone(),
+        );
+
+        tokio::spawn(connection_event_loop(
+            control_rx,
+            request_rx,
+            events,
+            routes.clone(),
+            conn,
+        ));
+
+        Self {
+            control_tx,
+            request_tx,
+            overflow,
+            gauges,
+            state,
+            subscriptions,
+            routes,
+            id_generator,
+            request_timeout: Default::default(),
+        }
     }
 
     /// Publishes a message over the network on given topic.
@@ -152,7 +313,7 @@ impl Client {
         ttl: Duration,
         prompt: bool,
     ) -> EmptyResponseFuture<Publish> {
-        let (request, response) = create_request(Publish {
+        let response = self.send(Publish {
             topic,
             message: message.into(),
             ttl_secs: ttl.as_secs() as u32,
@@ -160,18 +321,74 @@ impl Client {
             prompt,
         });
 
-        self.request(request);
-
         EmptyResponseFuture::new(response)
     }
 
+    /// Publishes a message over the network on given topic, retrying if the
+    /// connection is lost before the Relay responds, as configured by the
+    /// [`PublishRetryPolicy`].
+    ///
+    /// All attempts are sent with the same message ID, so that the Relay can
+    /// deduplicate the message, and resolve with the result of the last one.
+    pub async fn publish_with_retry(
+        &self,
+        topic: Topic,
+        message: impl Into<Arc<str>>,
+        tag: u32,
+        ttl: Duration,
+        prompt: bool,
+        policy: &PublishRetryPolicy,
+    ) -> Result<(), Error> {
+        let data = Publish {
+            topic,
+            message: message.into(),
+            ttl_secs: ttl.as_secs() as u32,
+            tag,
+            prompt,
+        };
+
+        let id = self.id_generator.next();
+        let mut state = self.state.clone();
+        let mut attempt = 1;
+
+        loop {
+            // Track the state changes from the moment the attempt is sent.
+            state.borrow_and_update();
+
+            let (request, response) = create_request(data.clone());
+            let result = self.send_request(request.with_id(id), response).await;
+
+            match result {
+                Err(err)
+                    if policy.should_retry(attempt, &err)
+                        && policy.wait_retry(&err, &mut state).await =>
+                {
+                    attempt += 1;
+                }
+
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+
     /// Subscribes on topic to receive messages.
     pub fn subscribe(&self, topic: Topic) -> ResponseFuture<Subscribe> {
-        let (request, response) = create_request(Subscribe { topic });
+        self.routes.hold([&topic]);
+        self.send(Subscribe { topic })
+    }
+
+    /// Subscribes on topic, returning a stream of the messages received on it.
+    ///
+    /// The messages of the topic are delivered to the stream instead of the
+    /// [`ConnectionHandler`] or [`ClientEvents`]. Dropping the stream
+    /// unsubscribes from the topic (see [`SubscriptionStream`]).
+    pub async fn subscribe_stream(&self, topic: Topic) -> Result<SubscriptionStream, Error> {
+        // Route the messages before subscribing, so that none are missed.
+        let stream = SubscriptionStream::new(self.clone(), topic.clone());
 
-        self.request(request);
+        self.send(Subscribe { topic }).await?;
 
-        response
+        Ok(stream)
     }
 
     /// Unsubscribes from a topic.
@@ -180,23 +397,19 @@ impl Client {
         topic: Topic,
         subscription_id: SubscriptionId,
     ) -> EmptyResponseFuture<Unsubscribe> {
-        let (request, response) = create_request(Unsubscribe {
+        self.routes.release([&topic]);
+
+        let response = self.send(Unsubscribe {
             topic,
             subscription_id,
         });
 
-        self.request(request);
-
         EmptyResponseFuture::new(response)
     }
 
     /// Fetch mailbox messages for a specific topic.
     pub fn fetch(&self, topic: Topic) -> ResponseFuture<FetchMessages> {
-        let (request, response) = create_request(FetchMessages { topic });
-
-        self.request(request);
-
-        response
+        self.send(FetchMessages { topic })
     }
 
     /// Fetch mailbox messages for a specific topic. Returns a [`Stream`].
@@ -206,13 +419,10 @@ impl Client {
 
     /// Subscribes on multiple topics to receive messages.
     pub fn batch_subscribe(&self, topics: impl Into<Vec<Topic>>) -> ResponseFuture<BatchSubscribe> {
-        let (request, response) = create_request(BatchSubscribe {
-            topics: topics.into(),
-        });
-
-        self.request(request);
+        let topics = topics.into();
 
-        response
+        self.routes.hold(&topics);
+        self.send(BatchSubscribe { topics })
     }
 
     /// Unsubscribes from multiple topics.
@@ -220,24 +430,21 @@ impl Client {
         &self,
         subscriptions: impl Into<Vec<Unsubscribe>>,
     ) -> EmptyResponseFuture<BatchUnsubscribe> {
-        let (request, response) = create_request(BatchUnsubscribe {
-            subscriptions: subscriptions.into(),
-        });
+        let subscriptions = subscriptions.into();
+
+        self.routes
+            .release(subscriptions.iter().map(|subscription| &subscription.topic));
 
-        self.request(request);
+        let response = self.send(BatchUnsubscribe { subscriptions });
 
         EmptyResponseFuture::new(response)
     }
 
     /// Fetch mailbox messages for multiple topics.
     pub fn batch_fetch(&self, topics: impl Into<Vec<Topic>>) -> ResponseFuture<BatchFetchMessages> {
-        let (request, response) = create_request(BatchFetchMessages {
+        self.send(BatchFetchMessages {
             topics: topics.into(),
-        });
-
-        self.request(request);
-
-        response
+        })
     }
 
     /// Acknowledge receipt of messages from a subscribed client.
@@ -245,13 +452,32 @@ impl Client {
         &self,
         receipts: impl Into<Vec<Receipt>>,
     ) -> ResponseFuture<BatchReceiveMessages> {
-        let (request, response) = create_request(BatchReceiveMessages {
+        self.send(BatchReceiveMessages {
             receipts: receipts.into(),
-        });
+        })
+    }
+
+    /// Returns the active subscriptions by topic.
+    ///
+    /// The client keeps track of the topics subscribed to and unsubscribed
+    /// from, and restores the subscriptions whenever a connection is
+    /// established, e.g. after an automatic reconnection. Since the Relay
+    /// assigns new IDs to the restored subscriptions, these are updated as
+    /// well.
+    pub fn subscriptions(&self) -> HashMap<Topic, SubscriptionId> {
+        self.subscriptions.snapshot()
+    }
 
-        self.request(request);
+    /// Returns a receiver of the current [`ConnectionState`], which can be
+    /// used to wait for its changes.
+    pub fn state(&self) -> watch::Receiver<ConnectionState> {
+        self.state.clone()
+    }
 
-        response
+    /// Returns the number of the messages in each of the client queues, e.g.
+    /// for monitoring the backpressure (see [`QueueConfig`]).
+    pub fn queue_depth(&self) -> QueueDepth {
+        self.gauges.depth(self.request_tx.capacity())
     }
 
     /// Opens a connection to the Relay.
@@ -259,9 +485,19 @@ impl Client {
         let (tx, rx) = oneshot::channel();
         let request = opts.as_ws_request()?;
 
+        let request_timeout = opts.request_timeout.map_or(0, |timeout| {
+            timeout.as_millis().clamp(1, u64::MAX as u128) as u64
+        });
+        self.request_timeout
+            .store(request_timeout, Ordering::Relaxed);
+
         if self
             .control_tx
-            .send(ConnectionControl::Connect { request, tx })
+            .send(ConnectionControl::Connect {
+                request: Box::new(request),
+                options: Box::new(opts.clone()),
+                tx,
+            })
             .is_ok()
         {
             rx.await.map_err(|_| Error::ChannelClosed)?
@@ -285,16 +521,140 @@ impl Client {
         }
     }
 
+    /// Sends the request, returning the response future with the default
+    /// request timeout applied.
+    ///
+    /// If the control queue is full, the request is either sent by the
+    /// response future once there's capacity, or fails, depending on the
+    /// [`OverflowPolicy`].
+    pub(crate) fn send<T>(&self, data: T) -> ResponseFuture<T>
+    where
+        T: RequestPayload,
+    {
+        let (request, response) = create_request(data);
+
+        self.send_request(request, response)
+    }
+
+    /// Same as [`Client::send()`], for an already created request.
+    fn send_request<T>(
+        &self,
+        request: OutboundRequest,
+        response: ResponseFuture<T>,
+    ) -> ResponseFuture<T>
+    where
+        T: RequestPayload,
+    {
+        let response = match self.try_request(request) {
+            Some(request) if self.overflow == OverflowPolicy::Wait => {
+                let request_tx = self.request_tx.clone();
+
+                response.with_send(
+                    async move {
+                        // The response future fails if the request is dropped.
+                        request_tx.send(request).await.ok();
+                    }
+                    .boxed(),
+                )
+            }
+
+            Some(request) => {
+                request
+                    .tx
+                    .send(Err(WebsocketClientError::QueueFull.into()))
+                    .ok();
+
+                response
+            }
+
+            None => response,
+        };
+
+        match self.request_timeout.load(Ordering::Relaxed) {
+            0 => response,
+            timeout => response.with_timeout(Duration::from_millis(timeout)),
+        }
+    }
+
+    /// Sends the request without waiting for the response, or for the control
+    /// queue capacity, in which case the request is sent in the background.
     pub(crate) fn request(&self, request: OutboundRequest) {
-        if let Err(err) = self
-            .control_tx
-            .send(ConnectionControl::OutboundRequest(request))
-        {
-            let ConnectionControl::OutboundRequest(request) = err.0 else {
-                unreachable!();
-            };
+        if let Some(request) = self.try_request(request) {
+            let request_tx = self.request_tx.clone();
 
-            request.tx.send(Err(Error::ChannelClosed)).ok();
+            tokio::spawn(async move {
+                request_tx.send(request).await.ok();
+            });
         }
     }
+
+    /// Sends the request if the control queue has capacity, otherwise returns
+    /// it back.
+    fn try_request(&self, request: OutboundRequest) -> Option<OutboundRequest> {
+        match self.request_tx.try_send(request) {
+            Ok(()) => None,
+
+            Err(TrySendError::Full(request)) => Some(request),
+
+            Err(TrySendError::Closed(request)) => {
+                request.tx.send(Err(Error::ChannelClosed)).ok();
+                None
+            }
+        }
+    }
+}
+
+/// Builder for a [`Client`] with a custom configuration.
+#[derive(Clone)]
+pub struct ClientBuilder {
+    queues: QueueConfig,
+    connector: Arc<dyn Connector>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            queues: QueueConfig::default(),
+            connector: Arc::new(TcpConnector),
+        }
+    }
+}
+
+impl std::fmt::Debug for ClientBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientBuilder")
+            .field("queues", &self.queues)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClientBuilder {
+    /// Sets the capacities of the client queues and the [`OverflowPolicy`].
+    pub fn with_queues(mut self, queues: QueueConfig) -> Self {
+        self.queues = queues;
+        self
+    }
+
+    /// Sets the [`Connector`] establishing the connections, e.g. to run the
+    /// client over a Unix socket, or an in-memory stream in tests.
+    pub fn with_connector(mut self, connector: impl Connector) -> Self {
+        self.connector = Arc::new(connector);
+        self
+    }
+
+    /// Creates a new [`Client`] with the provided handler.
+    pub fn build<T>(self, handler: T) -> Client
+    where
+        T: ConnectionHandler,
+    {
+        Client::with_event_sink(EventSink::Handler(Box::new(handler)), self)
+    }
+
+    /// Creates a new [`Client`] delivering the events to the returned
+    /// [`ClientEvents`] stream, see [`Client::with_events()`].
+    pub fn build_with_events(self, channel: EventChannel) -> (Client, ClientEvents) {
+        let (events, rx) = EventSink::new(channel);
+
+        (Client::with_event_sink(events, self), rx)
+    }
 }
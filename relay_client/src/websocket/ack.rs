@@ -0,0 +1,163 @@
+use {
+    super::InboundRequest,
+    relay_rpc::rpc::{GenericError, Subscription},
+    std::{
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+        time::Duration,
+    },
+    tokio::task::JoinHandle,
+};
+
+/// Errors responding to a subscription message with the [`MessageAck`].
+#[derive(Debug, thiserror::Error)]
+pub enum AckError {
+    #[error("Serialization failed: {0}")]
+    Serialization(serde_json::Error),
+
+    /// The response queue is full (see
+    /// [`QueueConfig::response_capacity`](super::QueueConfig::response_capacity)).
+    #[error("Queue is full")]
+    QueueFull,
+
+    /// The connection the message was received on is closed.
+    #[error("Internal error: Channel closed")]
+    ChannelClosed,
+}
+
+/// How the subscription messages received from the Relay are acknowledged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AckMode {
+    /// The messages are acknowledged as soon as they're delivered to the
+    /// [`ConnectionHandler`](super::ConnectionHandler) or the event stream.
+    #[default]
+    Auto,
+
+    /// The messages are acknowledged explicitly with the [`MessageAck`] of the
+    /// [`PublishedMessage`](super::PublishedMessage), e.g. once they have been
+    /// processed, so that the Relay doesn't consider them delivered if the
+    /// processing fails.
+    ///
+    /// If the timeout is set, the messages that haven't been acknowledged or
+    /// rejected within it are acknowledged automatically.
+    Manual { timeout: Option<Duration> },
+}
+
+/// Token for acknowledging or rejecting a subscription message in the
+/// [`AckMode::Manual`] mode.
+///
+/// The clones of the token refer to the same message, which can only be
+/// responded to once.
+#[derive(Debug, Clone)]
+pub struct MessageAck(Arc<Mutex<Option<PendingAck>>>);
+
+#[derive(Debug)]
+struct PendingAck {
+    request: InboundRequest<Subscription>,
+    // Holds a clone of the token, so it's aborted once the message has been
+    // responded to.
+    timeout: Option<JoinHandle<()>>,
+}
+
+impl MessageAck {
+    pub(super) fn new(request: InboundRequest<Subscription>, timeout: Option<Duration>) -> Self {
+        let ack = Self(Arc::new(Mutex::new(Some(PendingAck {
+            request,
+            timeout: None,
+        }))));
+
+        if let Some(timeout) = timeout {
+            let task = tokio::spawn({
+                let ack = ack.clone();
+
+                async move {
+                    tokio::time::sleep(timeout).await;
+                    ack.ack().ok();
+                }
+            });
+
+            if let Some(pending) = ack.lock().as_mut() {
+                pending.timeout = Some(task);
+            }
+        }
+
+        ack
+    }
+
+    /// Acknowledges the message. Returns `false` if the message has already
+    /// been acknowledged or rejected.
+    pub fn ack(&self) -> Result<bool, AckError> {
+        self.respond(Ok(true))
+    }
+
+    /// Rejects the message with the error. Returns `false` if the message has
+    /// already been acknowledged or rejected.
+    pub fn reject(&self, error: GenericError) -> Result<bool, AckError> {
+        self.respond(Err(error))
+    }
+
+    fn respond(&self, response: Result<bool, GenericError>) -> Result<bool, AckError> {
+        let Some(pending) = self.lock().take() else {
+            return Ok(false);
+        };
+
+        if let Some(timeout) = pending.timeout {
+            timeout.abort();
+        }
+
+        pending.request.send_response(response).map(|_| true)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<PendingAck>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        relay_rpc::{
+            domain::{MessageId, Topic},
+            rpc::SubscriptionData,
+        },
+        tokio::sync::mpsc,
+        tokio_tungstenite::tungstenite::Message,
+    };
+
+    fn request() -> (InboundRequest<Subscription>, mpsc::Receiver<Message>) {
+        let (tx, rx) = mpsc::channel(1);
+        let data = Subscription {
+            id: "sub".into(),
+            data: SubscriptionData {
+                topic: Topic::generate(),
+                message: "message".into(),
+                published_at: 0,
+                tag: 0,
+            },
+        };
+
+        (InboundRequest::new(MessageId::new(1), data, tx), rx)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout() {
+        let (request, mut rx) = request();
+        let _ack = MessageAck::new(request, Some(Duration::from_secs(1)));
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(rx.recv().await.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_cancelled() {
+        let (request, mut rx) = request();
+        let ack = MessageAck::new(request, Some(Duration::from_secs(1)));
+
+        assert!(ack.ack().unwrap());
+        assert!(rx.recv().await.is_some());
+
+        // The timeout task releases its token.
+        tokio::task::yield_now().await;
+        assert_eq!(Arc::strong_count(&ack.0), 1);
+    }
+}
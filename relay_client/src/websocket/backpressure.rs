@@ -0,0 +1,170 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// What happens to a request or a received message when the websocket client
+/// queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The response future waits for the queue capacity before sending the
+    /// request. The wait counts towards the request timeout, if any.
+    ///
+    /// The message waits for the capacity of its
+    /// [`SubscriptionStream`](super::SubscriptionStream), and reading from the
+    /// websocket pauses meanwhile.
+    #[default]
+    Wait,
+
+    /// The request fails immediately with
+    /// [`WebsocketClientError::QueueFull`](super::WebsocketClientError::QueueFull).
+    ///
+    /// The message is discarded, which is reported as an inbound error with
+    /// the same error. Instead of being acknowledged, the message is rejected
+    /// with [`GenericError::TooManyRequests`], so that the Relay doesn't
+    /// consider it delivered.
+    ///
+    /// [`GenericError::TooManyRequests`]: relay_rpc::rpc::GenericError::TooManyRequests
+    Fail,
+}
+
+/// Capacities of the bounded queues of the websocket client, which limit the
+/// memory used when the requests are made faster than the websocket can send
+/// them.
+///
+/// The queues hold at least one message, so a capacity of `0` is treated as
+/// `1`.
+#[derive(Debug, Clone)]
+pub struct QueueConfig {
+    /// Maximum number of the requests waiting to be processed by the
+    /// connection event loop.
+    pub control_capacity: usize,
+
+    /// Maximum number of the messages waiting to be written to the websocket,
+    /// per connection.
+    pub outbound_capacity: usize,
+
+    /// Maximum number of the responses to the inbound requests waiting to be
+    /// written to the websocket, per connection. Reading from the websocket
+    /// pauses while it's reached.
+    pub response_capacity: usize,
+
+    /// Maximum number of the received messages waiting to be taken from each
+    /// [`SubscriptionStream`](super::SubscriptionStream).
+    pub stream_capacity: usize,
+
+    /// What happens to a request when the control queue is full, or to a
+    /// message when the queue of its stream is full.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            control_capacity: 1024,
+            outbound_capacity: 1024,
+            response_capacity: 1024,
+            stream_capacity: 1024,
+            overflow: OverflowPolicy::Wait,
+        }
+    }
+}
+
+impl QueueConfig {
+    pub(super) fn control_capacity(&self) -> usize {
+        self.control_capacity.max(1)
+    }
+
+    pub(super) fn outbound_capacity(&self) -> usize {
+        self.outbound_capacity.max(1)
+    }
+
+    pub(super) fn response_capacity(&self) -> usize {
+        self.response_capacity.max(1)
+    }
+
+    pub(super) fn stream_capacity(&self) -> usize {
+        self.stream_capacity.max(1)
+    }
+
+    pub fn with_control_capacity(mut self, capacity: usize) -> Self {
+        self.control_capacity = capacity;
+        self
+    }
+
+    pub fn with_outbound_capacity(mut self, capacity: usize) -> Self {
+        self.outbound_capacity = capacity;
+        self
+    }
+
+    pub fn with_response_capacity(mut self, capacity: usize) -> Self {
+        self.response_capacity = capacity;
+        self
+    }
+
+    pub fn with_stream_capacity(mut self, capacity: usize) -> Self {
+        self.stream_capacity = capacity;
+        self
+    }
+
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+/// Number of the messages in each of the websocket client queues, returned by
+/// [`Client::queue_depth()`](super::Client::queue_depth).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueueDepth {
+    /// Requests waiting to be processed by the connection event loop.
+    pub control: usize,
+
+    /// Messages waiting to be written to the websocket.
+    pub outbound: usize,
+
+    /// Responses to the inbound requests waiting to be written to the
+    /// websocket.
+    pub responses: usize,
+
+    /// Requests buffered in the offline queue (see
+    /// [`OfflineQueueConfig`](super::OfflineQueueConfig)).
+    pub offline: usize,
+}
+
+/// Depths of the queues owned by the connection event loop, shared with the
+/// [`Client`](super::Client).
+#[derive(Debug, Clone)]
+pub(super) struct QueueGauges {
+    control_capacity: usize,
+    depths: Arc<[AtomicUsize; 3]>,
+}
+
+impl QueueGauges {
+    pub(super) fn new(control_capacity: usize) -> Self {
+        Self {
+            control_capacity,
+            depths: Default::default(),
+        }
+    }
+
+    pub(super) fn set(&self, outbound: usize, responses: usize, offline: usize) {
+        let [outbound_gauge, responses_gauge, offline_gauge] = &*self.depths;
+
+        outbound_gauge.store(outbound, Ordering::Relaxed);
+        responses_gauge.store(responses, Ordering::Relaxed);
+        offline_gauge.store(offline, Ordering::Relaxed);
+    }
+
+    /// Returns the depths, given the remaining capacity of the control queue.
+    pub(super) fn depth(&self, control_capacity: usize) -> QueueDepth {
+        let [outbound, responses, offline] = &*self.depths;
+
+        QueueDepth {
+            control: self.control_capacity - control_capacity,
+            outbound: outbound.load(Ordering::Relaxed),
+            responses: responses.load(Ordering::Relaxed),
+            offline: offline.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use {super::CloseFrame, tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode};
+
+/// Close code used by the Relay when the auth token is invalid or expired.
+const UNAUTHORIZED: u16 = 3000;
+
+/// Close code used by the Relay when the client exceeds the rate limit.
+const RATE_LIMITED: u16 = 3001;
+
+/// Close code used by the Relay when the project ID is not recognized.
+const PROJECT_NOT_FOUND: u16 = 4010;
+
+/// Reason for closing the connection, decoded from the close code sent by the
+/// Relay (see [`CloseReason::code()`](super::CloseReason::code)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelayCloseCode {
+    /// The connection was closed normally, e.g. by the client.
+    Normal,
+
+    /// The Relay is restarting or shutting down, and the client should
+    /// reconnect.
+    ServerRestart,
+
+    /// The Relay encountered an unexpected error.
+    ServerError,
+
+    /// The Relay is overloaded, and the client should reconnect later.
+    TryAgainLater,
+
+    /// The connection was lost without a closing handshake, e.g. after a
+    /// keepalive pong timeout.
+    ConnectionLost,
+
+    /// The auth token is invalid or expired.
+    Unauthorized,
+
+    /// The project ID is not recognized by the Relay.
+    ProjectNotFound,
+
+    /// The client exceeded the rate limit.
+    RateLimited,
+
+    /// Any other close code.
+    Other(u16),
+}
+
+impl RelayCloseCode {
+    /// Returns whether reconnecting may succeed, as opposed to the closing
+    /// reasons that require changing the connection options.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::ServerRestart
+                | Self::ServerError
+                | Self::TryAgainLater
+                | Self::ConnectionLost
+                | Self::RateLimited
+        )
+    }
+}
+
+impl From<CloseCode> for RelayCloseCode {
+    fn from(code: CloseCode) -> Self {
+        match code {
+            CloseCode::Normal => Self::Normal,
+            CloseCode::Away | CloseCode::Restart => Self::ServerRestart,
+            CloseCode::Error => Self::ServerError,
+            CloseCode::Again => Self::TryAgainLater,
+            CloseCode::Abnormal => Self::ConnectionLost,
+
+            code => match u16::from(code) {
+                UNAUTHORIZED => Self::Unauthorized,
+                RATE_LIMITED => Self::RateLimited,
+                PROJECT_NOT_FOUND => Self::ProjectNotFound,
+                code => Self::Other(code),
+            },
+        }
+    }
+}
+
+impl From<&CloseFrame<'_>> for RelayCloseCode {
+    fn from(frame: &CloseFrame<'_>) -> Self {
+        frame.code.into()
+    }
+}
+
+impl std::fmt::Display for RelayCloseCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normal => f.write_str("normal closure"),
+            Self::ServerRestart => f.write_str("server restart"),
+            Self::ServerError => f.write_str("server error"),
+            Self::TryAgainLater => f.write_str("try again later"),
+            Self::ConnectionLost => f.write_str("connection lost"),
+            Self::Unauthorized => f.write_str("unauthorized"),
+            Self::ProjectNotFound => f.write_str("project not found"),
+            Self::RateLimited => f.write_str("rate limited"),
+            Self::Other(code) => write!(f, "close code {code}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(
+            RelayCloseCode::from(CloseCode::Restart),
+            RelayCloseCode::ServerRestart
+        );
+        assert_eq!(
+            RelayCloseCode::from(CloseCode::from(3000)),
+            RelayCloseCode::Unauthorized
+        );
+        assert_eq!(
+            RelayCloseCode::from(CloseCode::from(4010)),
+            RelayCloseCode::ProjectNotFound
+        );
+        assert_eq!(
+            RelayCloseCode::from(CloseCode::from(4999)),
+            RelayCloseCode::Other(4999)
+        );
+        assert!(!RelayCloseCode::Unauthorized.is_retryable());
+    }
+}
@@ -0,0 +1,118 @@
+use {
+    socket2::SockRef,
+    std::time::Duration,
+    tokio::net::TcpStream,
+    tokio_tungstenite::tungstenite::protocol::WebSocketConfig,
+};
+
+/// Default maximum size of an inbound message.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Default maximum size of an inbound frame payload.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 << 20;
+
+/// Websocket protocol limits and socket options, applied by the
+/// [`TcpConnector`](super::TcpConnector).
+///
+/// Exceeding the inbound message or frame size limits closes the connection,
+/// reporting [`WebsocketClientError::MessageTooLarge`].
+///
+/// [`WebsocketClientError::MessageTooLarge`]: super::WebsocketClientError::MessageTooLarge
+#[derive(Debug, Clone)]
+pub struct WebsocketConfig {
+    /// Maximum size of an inbound message, or `None` for no limit. Defaults to
+    /// 64 MiB.
+    pub max_message_size: Option<usize>,
+
+    /// Maximum size of an inbound frame payload, or `None` for no limit.
+    /// Defaults to 16 MiB.
+    pub max_frame_size: Option<usize>,
+
+    /// Optional size of the socket send buffer (`SO_SNDBUF`). Defaults to the
+    /// OS setting.
+    pub send_buffer_size: Option<usize>,
+
+    /// Optional size of the socket receive buffer (`SO_RCVBUF`). Defaults to
+    /// the OS setting.
+    pub recv_buffer_size: Option<usize>,
+
+    /// Whether to disable Nagle's algorithm (`TCP_NODELAY`), sending the small
+    /// messages without delay. Disabled by default.
+    pub nodelay: bool,
+
+    /// Optional timeout for establishing the connection, including the TLS and
+    /// websocket handshakes. Applies to any
+    /// [`Connector`](super::Connector). Disabled by default.
+    pub connect_timeout: Option<Duration>,
+}
+
+impl Default for WebsocketConfig {
+    fn default() -> Self {
+        Self {
+            max_message_size: Some(DEFAULT_MAX_MESSAGE_SIZE),
+            max_frame_size: Some(DEFAULT_MAX_FRAME_SIZE),
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            nodelay: false,
+            connect_timeout: None,
+        }
+    }
+}
+
+impl WebsocketConfig {
+    pub fn with_max_message_size(mut self, size: impl Into<Option<usize>>) -> Self {
+        self.max_message_size = size.into();
+        self
+    }
+
+    pub fn with_max_frame_size(mut self, size: impl Into<Option<usize>>) -> Self {
+        self.max_frame_size = size.into();
+        self
+    }
+
+    pub fn with_send_buffer_size(mut self, size: impl Into<Option<usize>>) -> Self {
+        self.send_buffer_size = size.into();
+        self
+    }
+
+    pub fn with_recv_buffer_size(mut self, size: impl Into<Option<usize>>) -> Self {
+        self.recv_buffer_size = size.into();
+        self
+    }
+
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = nodelay;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.connect_timeout = timeout.into();
+        self
+    }
+
+    /// Returns the protocol configuration for `tungstenite`.
+    pub fn as_tungstenite(&self) -> WebSocketConfig {
+        WebSocketConfig {
+            max_message_size: self.max_message_size,
+            max_frame_size: self.max_frame_size,
+            ..Default::default()
+        }
+    }
+
+    /// Applies the socket options to the TCP stream.
+    pub fn configure_socket(&self, stream: &TcpStream) -> std::io::Result<()> {
+        stream.set_nodelay(self.nodelay)?;
+
+        let socket = SockRef::from(stream);
+
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+}
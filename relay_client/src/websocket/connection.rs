@@ -1,96 +1,252 @@
 use {
     super::{
+        ack::{AckMode, MessageAck},
+        backpressure::{OverflowPolicy, QueueConfig, QueueGauges},
+        close_code::RelayCloseCode,
+        events::{ClientEvent, EventSink},
         outbound::OutboundRequest,
-        stream::{create_stream, ClientStream},
-        ConnectionHandler,
+        queue::OfflineQueue,
+        state::ConnectionState,
+        stream::ClientStream,
+        subscription_stream::{MessageRoutes, RouteBacklog},
+        subscriptions::{SubscriptionRegistry, SubscriptionUpdate, TrackedResponse},
+        transport::{BoxTransport, Connector},
         TransportError,
         WebsocketClientError,
     },
     crate::{
         websocket::{stream::StreamEvent, PublishedMessage},
+        ConnectionOptions,
         Error,
         HttpRequest,
+        MessageIdGenerator,
+    },
+    ::http::StatusCode,
+    futures_util::{
+        future::BoxFuture,
+        stream::{FusedStream, FuturesUnordered},
+        FutureExt,
+        Stream,
+        StreamExt,
+    },
+    relay_rpc::{
+        domain::Topic,
+        rpc::{BatchSubscribe, GenericError, Params, RequestPayload, MAX_SUBSCRIPTION_BATCH_SIZE},
     },
-    futures_util::{stream::FusedStream, Stream, StreamExt},
     std::{
+        collections::VecDeque,
+        future::Future,
         pin::Pin,
+        sync::Arc,
         task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::{
+        sync::{
+            mpsc::{Receiver, UnboundedReceiver},
+            oneshot,
+            watch,
+        },
+        time::Sleep,
     },
-    tokio::sync::{mpsc::UnboundedReceiver, oneshot},
+    tokio_tungstenite::tungstenite::protocol::CloseFrame,
 };
 
+/// Maximum time to wait for the close frame to be sent, e.g. while the
+/// transport is stalled by a peer not reading. The connection is dropped
+/// without the closing handshake afterwards.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub(super) enum ConnectionControl {
     Connect {
-        request: HttpRequest<()>,
+        request: Box<HttpRequest<()>>,
+        options: Box<ConnectionOptions>,
         tx: oneshot::Sender<Result<(), Error>>,
     },
 
     Disconnect {
         tx: oneshot::Sender<Result<(), Error>>,
     },
-
-    OutboundRequest(OutboundRequest),
 }
 
-pub(super) async fn connection_event_loop<T>(
+pub(super) async fn connection_event_loop(
     mut control_rx: UnboundedReceiver<ConnectionControl>,
-    mut handler: T,
-) where
-    T: ConnectionHandler,
-{
-    let mut conn = Connection::new();
+    mut request_rx: Receiver<OutboundRequest>,
+    mut events: EventSink,
+    routes: MessageRoutes,
+    mut conn: Connection,
+) {
+    // Messages waiting for the capacity of their streams.
+    let mut backlog = RouteBacklog::default();
 
     loop {
+        // Reading from the websocket pauses while the events or the messages can't be
+        // delivered.
+        conn.pause_reading(events.is_blocked() || !backlog.is_empty());
+
         tokio::select! {
+            // Connecting and disconnecting don't wait for the outbound queue capacity.
             event = control_rx.recv() => {
                 match event {
                     Some(event) => match event {
-                        ConnectionControl::Connect { request, tx } => {
-                            let result = conn.connect(request).await;
+                        ConnectionControl::Connect { request, options, tx } => {
+                            conn.set_state(ConnectionState::Connecting);
+
+                            let result = conn.connect(*request, options).await;
 
                             if result.is_ok() {
-                                handler.connected();
+                                conn.set_state(ConnectionState::Connected);
+                                events.send(ClientEvent::Connected);
+                            } else {
+                                conn.set_state(ConnectionState::Disconnected);
                             }
 
                             tx.send(result).ok();
                         }
 
                         ConnectionControl::Disconnect { tx } => {
-                            tx.send(conn.disconnect().await).ok();
-                        }
+                            let result = conn.disconnect().await;
 
-                        ConnectionControl::OutboundRequest(request) => {
-                            conn.request(request);
+                            conn.set_state(ConnectionState::Disconnected);
+                            tx.send(result).ok();
                         }
                     }
 
                     // Control TX has been dropped, shutting down.
                     None => {
                         conn.disconnect().await.ok();
-                        handler.disconnected(None);
+                        conn.close_queue();
+                        conn.set_state(ConnectionState::Disconnected);
+                        events.send(ClientEvent::Disconnected(None));
+
+                        while events.is_blocked() {
+                            events.flush().await;
+                        }
+
                         break;
                     }
                 }
             }
 
+            // While the connection can't accept more requests, they wait in the request
+            // queue, so that the callers waiting for its capacity are held back.
+            Some(request) = request_rx.recv(), if conn.is_ready() => {
+                conn.request(request);
+            }
+
+            () = events.flush(), if events.is_blocked() => {}
+
+            () = backlog.flush(), if !backlog.is_empty() => {}
+
             event = conn.select_next_some() => {
                 match event {
-                    StreamEvent::InboundSubscriptionRequest(request) => {
-                        handler.message_received(PublishedMessage::from_request(&request));
-                        request.respond(Ok(true)).ok();
+                    ConnectionEvent::Stream(StreamEvent::InboundSubscriptionRequest(request)) => {
+                        let mut message = PublishedMessage::from_request(&request);
+
+                        let mut request = match conn.ack_mode() {
+                            AckMode::Auto => Some(request),
+
+                            AckMode::Manual { timeout } => {
+                                message.ack = Some(MessageAck::new(request, timeout));
+                                None
+                            }
+                        };
+
+                        let ack = message.ack.clone();
+
+                        match routes.route(message) {
+                            Ok(mut full) => match conn.overflow() {
+                                OverflowPolicy::Wait => backlog.append(&mut full),
+
+                                OverflowPolicy::Fail if !full.is_empty() => {
+                                    for _ in 0..full.len() {
+                                        let error = WebsocketClientError::QueueFull.into();
+                                        events.send(ClientEvent::InboundError(Arc::new(error)));
+                                    }
+
+                                    // Reject the message dropped by any of the streams, so that
+                                    // the Relay doesn't consider it delivered.
+                                    let error = GenericError::TooManyRequests;
+
+                                    if let Some(request) = request.take() {
+                                        request.respond(Err(error)).ok();
+                                    } else if let Some(ack) = ack {
+                                        ack.reject(error).ok();
+                                    }
+                                }
+
+                                OverflowPolicy::Fail => {}
+                            },
+
+                            Err(message) => events.send(ClientEvent::Message(message)),
+                        }
+
+                        if let Some(request) = request {
+                            request.respond(Ok(true)).ok();
+                        }
+                    }
+
+                    ConnectionEvent::SubscriptionResponse(response) => {
+                        if let Some((topics, error)) = conn.complete(response) {
+                            events
+                                .send(ClientEvent::ResubscribeFailed {
+                                    topics,
+                                    error: Arc::new(error),
+                                });
+                        }
                     }
 
-                    StreamEvent::InboundError(error) => {
-                        handler.inbound_error(error);
+                    ConnectionEvent::Stream(StreamEvent::InboundError(error)) => {
+                        events.send(ClientEvent::InboundError(Arc::new(error)));
                     }
 
-                    StreamEvent::OutboundError(error) => {
-                        handler.outbound_error(error);
+                    ConnectionEvent::Stream(StreamEvent::OutboundError(error)) => {
+                        events.send(ClientEvent::OutboundError(Arc::new(error)));
                     }
 
-                    StreamEvent::ConnectionClosed(frame) => {
-                        handler.disconnected(frame);
+                    ConnectionEvent::Stream(StreamEvent::Pong(latency)) => {
+                        events.send(ClientEvent::Pong(latency));
+                    }
+
+                    ConnectionEvent::Stream(StreamEvent::ConnectionClosed(frame)) => {
                         conn.reset();
+
+                        let reconnect = conn.schedule_reconnect(frame.as_ref());
+
+                        if reconnect.is_some() {
+                            conn.set_state(ConnectionState::Reconnecting);
+                        } else {
+                            conn.set_state(ConnectionState::Disconnected);
+                        }
+
+                        events.send(ClientEvent::Disconnected(frame));
+
+                        if let Some((attempt, delay)) = reconnect {
+                            events.send(ClientEvent::Reconnecting { attempt, delay });
+                        }
+                    }
+
+                    // Nothing to do, the request queue is polled again on the next
+                    // iteration.
+                    ConnectionEvent::Ready => {}
+
+                    ConnectionEvent::ReconnectDue => {
+                        match conn.reconnect().await {
+                            ReconnectResult::Connected { attempts } => {
+                                conn.set_state(ConnectionState::Connected);
+                                events.send(ClientEvent::Connected);
+                                events.send(ClientEvent::Reconnected { attempts });
+                            }
+
+                            ReconnectResult::Retrying { attempt, delay } => {
+                                events.send(ClientEvent::Reconnecting { attempt, delay });
+                            }
+
+                            ReconnectResult::Failed(error) => {
+                                conn.set_state(ConnectionState::Disconnected);
+                                events.send(ClientEvent::ReconnectFailed(Arc::new(error)));
+                            }
+                        }
                     }
                 }
             }
@@ -98,40 +254,259 @@ pub(super) async fn connection_event_loop<T>(
     }
 }
 
-struct Connection {
-    stream: Option<ClientStream>,
+/// Events produced by polling the [`Connection`].
+pub(super) enum ConnectionEvent {
+    Stream(StreamEvent),
+
+    /// Response to a request changing the subscriptions.
+    SubscriptionResponse(TrackedResponse),
+
+    /// The delay before the next reconnection attempt has elapsed.
+    ReconnectDue,
+
+    /// The connection can accept more requests again (see
+    /// [`Connection::is_ready()`]).
+    Ready,
+}
+
+enum ReconnectResult {
+    Connected { attempts: u32 },
+    Retrying { attempt: u32, delay: Duration },
+    Failed(Error),
+}
+
+/// State of the automatic reconnection.
+struct Reconnect {
+    attempt: u32,
+    delay: Pin<Box<Sleep>>,
+}
+
+pub(super) struct Connection {
+    stream: Option<ClientStream<BoxTransport>>,
+    options: Option<ConnectionOptions>,
+    reconnect: Option<Reconnect>,
+    subscriptions: SubscriptionRegistry,
+    responses: FuturesUnordered<BoxFuture<'static, TrackedResponse>>,
+    resubscriptions: VecDeque<Vec<Topic>>,
+    queue: Option<OfflineQueue>,
+    queues: QueueConfig,
+    gauges: QueueGauges,
+    state: watch::Sender<ConnectionState>,
+    connector: Arc<dyn Connector>,
+    id_generator: MessageIdGenerator,
+    read_paused: bool,
 }
 
 impl Connection {
-    fn new() -> Self {
-        Self { stream: None }
+    pub(super) fn new(
+        subscriptions: SubscriptionRegistry,
+        queues: QueueConfig,
+        gauges: QueueGauges,
+        state: watch::Sender<ConnectionState>,
+        connector: Arc<dyn Connector>,
+        id_generator: MessageIdGenerator,
+    ) -> Self {
+        Self {
+            stream: None,
+            options: None,
+            reconnect: None,
+            subscriptions,
+            responses: FuturesUnordered::new(),
+            resubscriptions: VecDeque::new(),
+            queue: None,
+            queues,
+            gauges,
+            state,
+            connector,
+            id_generator,
+            read_paused: false,
+        }
     }
 
-    async fn connect(&mut self, request: HttpRequest<()>) -> Result<(), Error> {
-        if let Some(mut stream) = self.stream.take() {
-            stream.close(None).await?;
+    /// Pauses or resumes reading from the websocket, which also applies to the
+    /// streams of the later reconnections.
+    fn pause_reading(&mut self, paused: bool) {
+        self.read_paused = paused;
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        self.state.send_replace(state);
+    }
+
+    async fn connect(
+        &mut self,
+        request: HttpRequest<()>,
+        options: Box<ConnectionOptions>,
+    ) -> Result<(), Error> {
+        self.reconnect = None;
+
+        if let Some(stream) = self.stream.take() {
+            close_stream(stream).await?;
         }
 
-        self.stream = Some(create_stream(request).await?);
+        let stream = self.open_stream(request, &options).await?;
+
+        self.stream = Some(stream);
+        self.resubscribe();
+
+        // Keep the requests queued until now, so that they're sent in order.
+        self.queue = match (self.queue.take(), &options.offline_queue) {
+            (Some(mut queue), Some(config)) => {
+                queue.set_config(config);
+                Some(queue)
+            }
+
+            // The queue is disabled by the new options, so send the requests right
+            // away.
+            (Some(mut queue), None) => {
+                while let Some(request) = queue.pop() {
+                    self.send(request);
+                }
+
+                None
+            }
+
+            (None, config) => config.as_ref().map(OfflineQueue::new),
+        };
+
+        self.flush_queue();
+        self.options = Some(*options);
 
         Ok(())
     }
 
+    /// Returns a future opening the stream. It doesn't borrow the connection,
+    /// which can't be shared between threads.
+    fn open_stream(
+        &self,
+        request: HttpRequest<()>,
+        options: &ConnectionOptions,
+    ) -> impl Future<Output = Result<ClientStream<BoxTransport>, WebsocketClientError>> {
+        let connect = self.connector.connect(request, options);
+        let timeout = options.websocket.connect_timeout;
+        let keepalive = options.keepalive.clone();
+        let queues = self.queues.clone();
+        let id_generator = self.id_generator.clone();
+
+        async move {
+            let transport = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, connect)
+                    .await
+                    .map_err(|_| WebsocketClientError::ConnectTimeout)??,
+
+                None => connect.await?,
+            };
+
+            Ok(ClientStream::new(transport)
+                .with_id_generator(id_generator)
+                .with_keepalive(keepalive.as_ref())
+                .with_queues(&queues))
+        }
+    }
+
     async fn disconnect(&mut self) -> Result<(), Error> {
         let stream = self.stream.take();
+        let reconnecting = self.reconnect.take().is_some();
+
+        self.options = None;
+        self.resubscriptions.clear();
 
         match stream {
-            Some(mut stream) => stream.close(None).await,
+            Some(stream) => close_stream(stream).await,
+
+            // Disconnecting cancels the reconnection.
+            None if reconnecting => Ok(()),
 
             None => Err(WebsocketClientError::ClosingFailed(TransportError::AlreadyClosed).into()),
         }
     }
 
     fn request(&mut self, request: OutboundRequest) {
-        match &mut self.stream {
-            Some(stream) => stream.send_raw(request),
+        // Intercept the responses to the requests changing the subscriptions to keep
+        // the registry up to date.
+        let request = match SubscriptionUpdate::from_params(&request.params) {
+            Some(update) => OutboundRequest {
+                id: request.id,
+                ..self.track(update, request.params, Some(request.tx))
+            },
+            None => request,
+        };
+
+        self.send(request);
+    }
+
+    fn track(
+        &mut self,
+        update: SubscriptionUpdate,
+        params: Params,
+        tx: Option<oneshot::Sender<Result<serde_json::Value, Error>>>,
+    ) -> OutboundRequest {
+        let (tx, response) = TrackedResponse::track(update, tx);
 
-            None => {
+        self.responses.push(response);
+
+        OutboundRequest::new(params, tx)
+    }
+
+    fn complete(&mut self, response: TrackedResponse) -> Option<(Vec<Topic>, Error)> {
+        response.complete(&self.subscriptions)
+    }
+
+    /// Restores the subscriptions from the registry on the new connection.
+    /// The requests are sent ahead of any other ones as the outbound queue
+    /// capacity allows (see [`Connection::flush_queue()`]).
+    fn resubscribe(&mut self) {
+        self.resubscriptions = self
+            .subscriptions
+            .topics()
+            .chunks(MAX_SUBSCRIPTION_BATCH_SIZE)
+            .map(<[Topic]>::to_vec)
+            .collect();
+    }
+
+    fn send(&mut self, request: OutboundRequest) {
+        match (&mut self.stream, &mut self.queue) {
+            (Some(stream), _) => stream.send_raw(request),
+
+            (None, Some(queue)) => queue.push(request),
+
+            (None, None) => {
+                request
+                    .tx
+                    .send(Err(WebsocketClientError::NotConnected.into()))
+                    .ok();
+            }
+        }
+    }
+
+    /// Sends the resubscription requests, and then the requests queued while
+    /// disconnected, as long as the outbound queue of the stream has capacity.
+    fn flush_queue(&mut self) {
+        while self.stream.as_ref().is_some_and(ClientStream::has_capacity) {
+            let request = if let Some(topics) = self.resubscriptions.pop_front() {
+                let params = BatchSubscribe {
+                    topics: topics.clone(),
+                }
+                .into_params();
+
+                self.track(SubscriptionUpdate::Resubscribe(topics), params, None)
+            } else if let Some(request) = self.queue.as_mut().and_then(OfflineQueue::pop) {
+                request
+            } else {
+                break;
+            };
+
+            self.send(request);
+        }
+    }
+
+    /// Fails the queued requests with [`WebsocketClientError::NotConnected`]
+    /// once the client is shut down. Until then, the requests stay queued
+    /// across the disconnections and failed connection attempts, until they
+    /// expire.
+    fn close_queue(&mut self) {
+        if let Some(mut queue) = self.queue.take() {
+            while let Some(request) = queue.pop() {
                 request
                     .tx
                     .send(Err(WebsocketClientError::NotConnected.into()))
@@ -140,22 +515,190 @@ impl Connection {
         }
     }
 
+    /// Returns whether the connection can accept more requests. The
+    /// resubscription and queued requests must be flushed first to keep the
+    /// order.
+    fn is_ready(&self) -> bool {
+        match &self.stream {
+            Some(stream) => {
+                stream.has_capacity()
+                    && self.resubscriptions.is_empty()
+                    && self.queue.as_ref().is_none_or(OfflineQueue::is_empty)
+            }
+
+            None => true,
+        }
+    }
+
+    fn update_gauges(&self) {
+        let (outbound, responses) = self
+            .stream
+            .as_ref()
+            .map(|stream| (stream.outbound_len(), stream.responses_len()))
+            .unwrap_or_default();
+        let offline = self.queue.as_ref().map_or(0, OfflineQueue::len);
+
+        self.gauges.set(outbound, responses, offline);
+    }
+
+    fn ack_mode(&self) -> AckMode {
+        self.options
+            .as_ref()
+            .map(|options| options.ack_mode)
+            .unwrap_or_default()
+    }
+
+    fn overflow(&self) -> OverflowPolicy {
+        self.queues.overflow
+    }
+
     fn reset(&mut self) {
         self.stream = None;
+
+        // Restored from the registry again on the next connection.
+        self.resubscriptions.clear();
+    }
+
+    /// Schedules the first reconnection attempt if the reconnect policy allows
+    /// reconnecting after the connection is closed with the frame. Returns the
+    /// attempt number and the delay before it.
+    /// The connection closed by the Relay due to an invalid or expired auth
+    /// token is only reconnected with the [`AuthRefresh`](crate::AuthRefresh)
+    /// hook.
+    fn schedule_reconnect(&mut self, frame: Option<&CloseFrame<'_>>) -> Option<(u32, Duration)> {
+        let options = self.options.as_ref()?;
+        let policy = options.reconnect_policy.as_ref()?;
+
+        let reconnect = match frame.map(RelayCloseCode::from) {
+            Some(RelayCloseCode::Unauthorized) => options.auth_refresh.is_some(),
+            _ => policy.should_reconnect(frame),
+        };
+
+        if reconnect {
+            Some(self.schedule_attempt(1))
+        } else {
+            None
+        }
+    }
+
+    fn schedule_attempt(&mut self, attempt: u32) -> (u32, Duration) {
+        let delay = self
+            .options
+            .as_ref()
+            .and_then(|options| options.reconnect_policy.as_ref())
+            .map(|policy| policy.delay(attempt))
+            .unwrap_or_default();
+
+        self.reconnect = Some(Reconnect {
+            attempt,
+            delay: Box::pin(tokio::time::sleep(delay)),
+        });
+
+        (attempt, delay)
+    }
+
+    async fn reconnect(&mut self) -> ReconnectResult {
+        // Reissue the auth token first, since the one of the previous connection
+        // may have expired by now.
+        let refreshed = self
+            .options
+            .as_mut()
+            .map_or(Ok(()), ConnectionOptions::refresh_auth);
+
+        let (Some(reconnect), Some(options)) = (self.reconnect.take(), &self.options) else {
+            return ReconnectResult::Failed(WebsocketClientError::NotConnected.into());
+        };
+
+        let request = match refreshed {
+            Ok(()) => options.as_ws_request(),
+            Err(err) => Err(err.into()),
+        };
+
+        let result = match request {
+            Ok(request) => self.open_stream(request, options).await.map_err(Into::into),
+            Err(err) => Err(err.into()),
+        };
+
+        match result {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.resubscribe();
+                self.flush_queue();
+
+                ReconnectResult::Connected {
+                    attempts: reconnect.attempt,
+                }
+            }
+
+            Err(error) => {
+                // Retrying with the same rejected auth token is pointless.
+                let can_retry = (options.auth_refresh.is_some() || !is_unauthorized(&error))
+                    && options
+                        .reconnect_policy
+                        .as_ref()
+                        .is_some_and(|policy| policy.can_retry(reconnect.attempt));
+
+                if can_retry {
+                    let (attempt, delay) = self.schedule_attempt(reconnect.attempt + 1);
+
+                    ReconnectResult::Retrying { attempt, delay }
+                } else {
+                    ReconnectResult::Failed(error)
+                }
+            }
+        }
     }
 }
 
-impl Stream for Connection {
-    type Item = StreamEvent;
+/// Returns whether the Relay rejected the websocket handshake due to an invalid
+/// or expired auth token.
+fn is_unauthorized(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::WebsocketClient(WebsocketClientError::ConnectionFailed(TransportError::Http(response)))
+            if response.status() == StatusCode::UNAUTHORIZED
+    )
+}
+
+async fn close_stream(mut stream: ClientStream<BoxTransport>) -> Result<(), Error> {
+    tokio::time::timeout(CLOSE_TIMEOUT, stream.close(None))
+        .await
+        .unwrap_or(Ok(()))
+}
+
+impl Connection {
+    fn poll_event(&mut self, cx: &mut Context<'_>) -> Poll<Option<ConnectionEvent>> {
+        if let Some(queue) = &mut self.queue {
+            queue.poll_expired(cx);
+        }
+
+        self.flush_queue();
+
+        if let Poll::Ready(Some(response)) = self.responses.poll_next_unpin(cx) {
+            return Poll::Ready(Some(ConnectionEvent::SubscriptionResponse(response)));
+        }
+
+        if let Some(reconnect) = &mut self.reconnect {
+            return reconnect
+                .delay
+                .poll_unpin(cx)
+                .map(|_| Some(ConnectionEvent::ReconnectDue));
+        }
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         if let Some(stream) = &mut self.stream {
             if stream.is_terminated() {
+                // The stream is terminated without a close frame after a transport
+                // error, so report it as closed.
                 self.stream = None;
 
-                Poll::Pending
+                Poll::Ready(Some(ConnectionEvent::Stream(
+                    StreamEvent::ConnectionClosed(None),
+                )))
             } else {
-                stream.poll_next_unpin(cx)
+                stream.pause_reading(self.read_paused);
+                stream
+                    .poll_next_unpin(cx)
+                    .map(|event| event.map(ConnectionEvent::Stream))
             }
         } else {
             Poll::Pending
@@ -163,6 +706,26 @@ impl Stream for Connection {
     }
 }
 
+impl Stream for Connection {
+    type Item = ConnectionEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let was_ready = self.is_ready();
+
+        let event = match self.poll_event(cx) {
+            Poll::Pending if !was_ready && self.is_ready() => {
+                Poll::Ready(Some(ConnectionEvent::Ready))
+            }
+
+            event => event,
+        };
+
+        self.update_gauges();
+
+        event
+    }
+}
+
 impl FusedStream for Connection {
     fn is_terminated(&self) -> bool {
         false
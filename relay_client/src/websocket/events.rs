@@ -0,0 +1,252 @@
+use {
+    super::{CloseFrame, ConnectionHandler, PublishedMessage},
+    crate::Error,
+    futures_util::{Stream, StreamExt},
+    relay_rpc::domain::Topic,
+    std::{
+        collections::VecDeque,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::sync::{
+        broadcast,
+        mpsc::{self, error::TrySendError},
+    },
+    tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream, ReceiverStream},
+};
+
+/// Events produced by the [`Client`](super::Client) created with
+/// [`Client::with_events()`](super::Client::with_events). See
+/// [`ConnectionHandler`] for the description of each event.
+#[derive(Debug, Clone)]
+pub enum ClientEvent {
+    Connected,
+    Disconnected(Option<CloseFrame<'static>>),
+    Message(PublishedMessage),
+    InboundError(Arc<Error>),
+    OutboundError(Arc<Error>),
+    Pong(Duration),
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    Reconnected {
+        attempts: u32,
+    },
+    ReconnectFailed(Arc<Error>),
+    ResubscribeFailed {
+        topics: Vec<Topic>,
+        error: Arc<Error>,
+    },
+
+    /// The consumer of a [`EventChannel::Broadcast`] channel fell behind and
+    /// missed the number of the oldest events.
+    Lagged(u64),
+}
+
+/// Channel delivering the [`ClientEvent`]s. The channels hold at least one
+/// event, so a capacity of `0` is treated as `1`.
+#[derive(Debug, Clone, Copy)]
+pub enum EventChannel {
+    /// Single consumer channel with the capacity. The client stops reading
+    /// from the websocket while the channel is full, so a slow consumer slows
+    /// down the connection instead of losing events.
+    ///
+    /// The responses to the [`Client`](super::Client) requests are read from
+    /// the same websocket, so the consumer must not wait for a response in
+    /// between receiving the events, or it may wait forever. Spawn such
+    /// requests instead, or use [`EventChannel::Broadcast`].
+    Bounded(usize),
+
+    /// Multiple consumer channel with the capacity (see
+    /// [`ClientEvents::subscribe()`]). The consumers that fall behind miss the
+    /// oldest events, which is reported with [`ClientEvent::Lagged`].
+    Broadcast(usize),
+}
+
+enum EventReceiver {
+    Bounded(ReceiverStream<ClientEvent>),
+
+    Broadcast {
+        // Holding a sender would keep the channel open, so the new receivers are
+        // created from this one instead.
+        origin: broadcast::Receiver<ClientEvent>,
+        rx: BroadcastStream<ClientEvent>,
+    },
+}
+
+/// [`Stream`] of the [`ClientEvent`]s, which ends once the
+/// [`Client`](super::Client) and all of its clones are dropped.
+pub struct ClientEvents(EventReceiver);
+
+impl ClientEvents {
+    /// Returns another stream receiving the events sent from now on, if the
+    /// channel is [`EventChannel::Broadcast`].
+    pub fn subscribe(&self) -> Option<Self> {
+        match &self.0 {
+            EventReceiver::Bounded(_) => None,
+
+            EventReceiver::Broadcast { origin, .. } => Some(Self(EventReceiver::Broadcast {
+                origin: origin.resubscribe(),
+                rx: BroadcastStream::new(origin.resubscribe()),
+            })),
+        }
+    }
+}
+
+impl Stream for ClientEvents {
+    type Item = ClientEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.0 {
+            EventReceiver::Bounded(rx) => rx.poll_next_unpin(cx),
+
+            EventReceiver::Broadcast { rx, .. } => rx.poll_next_unpin(cx).map(|event| {
+                event.map(|event| {
+                    event.unwrap_or_else(|BroadcastStreamRecvError::Lagged(skipped)| {
+                        ClientEvent::Lagged(skipped)
+                    })
+                })
+            }),
+        }
+    }
+}
+
+/// Destination of the events produced by the connection event loop.
+pub(super) enum EventSink {
+    Handler(Box<dyn ConnectionHandler>),
+    Bounded {
+        tx: mpsc::Sender<ClientEvent>,
+        // Events waiting for the channel capacity. The connection event loop
+        // keeps handling the control messages and the requests meanwhile.
+        backlog: VecDeque<ClientEvent>,
+    },
+    Broadcast(broadcast::Sender<ClientEvent>),
+}
+
+impl EventSink {
+    pub(super) fn new(channel: EventChannel) -> (Self, ClientEvents) {
+        match channel {
+            EventChannel::Bounded(capacity) => {
+                let (tx, rx) = mpsc::channel(capacity.max(1));
+
+                (
+                    Self::Bounded {
+                        tx,
+                        backlog: VecDeque::new(),
+                    },
+                    ClientEvents(EventReceiver::Bounded(ReceiverStream::new(rx))),
+                )
+            }
+
+            EventChannel::Broadcast(capacity) => {
+                let (tx, rx) = broadcast::channel(capacity.max(1));
+
+                (
+                    Self::Broadcast(tx),
+                    ClientEvents(EventReceiver::Broadcast {
+                        origin: rx.resubscribe(),
+                        rx: BroadcastStream::new(rx),
+                    }),
+                )
+            }
+        }
+    }
+
+    /// Sends the event, or queues it until there's capacity in the
+    /// [`EventChannel::Bounded`] channel (see [`EventSink::flush()`]).
+    pub(super) fn send(&mut self, event: ClientEvent) {
+        match self {
+            Self::Handler(handler) => dispatch(handler.as_mut(), event),
+
+            // The consumers may be gone, in which case the events are discarded.
+            Self::Bounded { tx, backlog } => {
+                if !backlog.is_empty() {
+                    backlog.push_back(event);
+                } else if let Err(TrySendError::Full(event)) = tx.try_send(event) {
+                    backlog.push_back(event);
+                }
+            }
+
+            Self::Broadcast(tx) => {
+                tx.send(event).ok();
+            }
+        }
+    }
+
+    /// Returns whether there are events waiting for the channel capacity.
+    pub(super) fn is_blocked(&self) -> bool {
+        match self {
+            Self::Bounded { backlog, .. } => !backlog.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Waits for the channel capacity, and sends the oldest queued event.
+    pub(super) async fn flush(&mut self) {
+        if let Self::Bounded { tx, backlog } = self {
+            if backlog.is_empty() {
+                return;
+            }
+
+            match tx.reserve().await {
+                Ok(permit) => {
+                    if let Some(event) = backlog.pop_front() {
+                        permit.send(event);
+                    }
+                }
+
+                Err(_) => backlog.clear(),
+            }
+        }
+    }
+}
+
+/// Adapts the events to the [`ConnectionHandler`] callbacks.
+fn dispatch(handler: &mut dyn ConnectionHandler, event: ClientEvent) {
+    // The handler is the only owner of the errors, so these can always be
+    // unwrapped.
+    let error = |error| Arc::try_unwrap(error).ok();
+
+    match event {
+        ClientEvent::Connected => handler.connected(),
+
+        ClientEvent::Disconnected(frame) => handler.disconnected(frame),
+
+        ClientEvent::Message(message) => handler.message_received(message),
+
+        ClientEvent::InboundError(err) => {
+            if let Some(err) = error(err) {
+                handler.inbound_error(err);
+            }
+        }
+
+        ClientEvent::OutboundError(err) => {
+            if let Some(err) = error(err) {
+                handler.outbound_error(err);
+            }
+        }
+
+        ClientEvent::Pong(latency) => handler.pong_received(latency),
+
+        ClientEvent::Reconnecting { attempt, delay } => handler.reconnecting(attempt, delay),
+
+        ClientEvent::Reconnected { attempts } => handler.reconnected(attempts),
+
+        ClientEvent::ReconnectFailed(err) => {
+            if let Some(err) = error(err) {
+                handler.reconnect_failed(err);
+            }
+        }
+
+        ClientEvent::ResubscribeFailed { topics, error: err } => {
+            if let Some(err) = error(err) {
+                handler.resubscribe_failed(topics, err);
+            }
+        }
+
+        ClientEvent::Lagged(_) => {}
+    }
+}
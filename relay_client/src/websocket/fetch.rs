@@ -1,5 +1,5 @@
 use {
-    super::{create_request, Client, ResponseFuture},
+    super::{Client, ResponseFuture},
     crate::Error,
     futures_util::{FutureExt, Stream},
     relay_rpc::{
@@ -90,10 +90,7 @@ impl Stream for FetchMessageStream {
             } else if self.has_more {
                 // We have neither a batch, or a batch future, but `has_more` flag is set. Set
                 // up a future to receive the next batch.
-                let (request, batch_fut) = create_request(self.request.clone());
-
-                self.client.request(request);
-                self.batch_fut = Some(batch_fut);
+                self.batch_fut = Some(self.client.send(self.request.clone()));
             } else {
                 // The stream can't produce any more items, since it doesn't have neither a
                 // batch of data or a future for receiving the next batch, and `has_more` flag
@@ -1,10 +1,11 @@
 use {
+    super::{AckError, WebsocketClientError},
     crate::Error,
     relay_rpc::{
         domain::MessageId,
         rpc::{ErrorResponse, Payload, RequestPayload, Response, SuccessfulResponse},
     },
-    tokio::sync::mpsc::UnboundedSender,
+    tokio::sync::mpsc::{error::TrySendError, Sender},
     tokio_tungstenite::tungstenite::Message,
 };
 
@@ -18,7 +19,7 @@ use {
 #[derive(Debug)]
 pub struct InboundRequest<T> {
     id: MessageId,
-    tx: UnboundedSender<Message>,
+    tx: Sender<Message>,
     data: T,
 }
 
@@ -26,7 +27,7 @@ impl<T> InboundRequest<T>
 where
     T: RequestPayload,
 {
-    pub(super) fn new(id: MessageId, data: T, tx: UnboundedSender<Message>) -> Self {
+    pub(super) fn new(id: MessageId, data: T, tx: Sender<Message>) -> Self {
         Self { id, tx, data }
     }
 
@@ -43,22 +44,38 @@ where
     /// `Result<T::Response, T::Error>` (see [`RequestPayload`] trait for
     /// details).
     ///
-    /// Returns an error if the response can't be serialized, or if the
-    /// underlying channel is closed.
+    /// Returns an error if the response can't be serialized, if the
+    /// underlying channel is closed, or if it's full (see
+    /// [`QueueConfig::response_capacity`](super::QueueConfig::response_capacity)).
     pub fn respond(self, response: Result<T::Response, T::Error>) -> Result<(), Error> {
+        self.send_response(response).map_err(|err| match err {
+            AckError::Serialization(err) => Error::Serialization(err),
+            AckError::QueueFull => WebsocketClientError::QueueFull.into(),
+            AckError::ChannelClosed => Error::ChannelClosed,
+        })
+    }
+
+    /// Same as [`InboundRequest::respond()`], with a lighter error type.
+    pub(super) fn send_response(
+        self,
+        response: Result<T::Response, T::Error>,
+    ) -> Result<(), AckError> {
         let response = match response {
             Ok(data) => Response::Success(SuccessfulResponse::new(
                 self.id,
-                serde_json::to_value(data).map_err(Error::Serialization)?,
+                serde_json::to_value(data).map_err(AckError::Serialization)?,
             )),
 
             Err(err) => Response::Error(ErrorResponse::new(self.id, err.into())),
         };
 
         let message = Message::Text(
-            serde_json::to_string(&Payload::Response(response)).map_err(Error::Serialization)?,
+            serde_json::to_string(&Payload::Response(response)).map_err(AckError::Serialization)?,
         );
 
-        self.tx.send(message).map_err(|_| Error::ChannelClosed)
+        self.tx.try_send(message).map_err(|err| match err {
+            TrySendError::Full(_) => AckError::QueueFull,
+            TrySendError::Closed(_) => AckError::ChannelClosed,
+        })
     }
 }
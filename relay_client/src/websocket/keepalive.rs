@@ -0,0 +1,140 @@
+use {
+    futures_util::FutureExt,
+    std::{
+        borrow::Cow,
+        pin::Pin,
+        task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::time::{interval_at, Instant, Interval, MissedTickBehavior, Sleep},
+    tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+};
+
+/// Reason of the [`CloseFrame`] reported when the connection is closed because
+/// the Relay didn't respond to a keepalive ping in time.
+pub const PONG_TIMEOUT_REASON: &str = "pong timeout";
+
+/// Configuration of the keepalive pings, used to measure the round-trip time
+/// and to detect dead connections, e.g. half-open TCP connections on mobile
+/// networks.
+#[derive(Debug, Clone)]
+pub struct KeepaliveConfig {
+    /// Interval between the pings.
+    pub interval: Duration,
+
+    /// Time to wait for the pong before the connection is considered dead and
+    /// is closed.
+    pub timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Returns whether the connection was closed because the Relay didn't respond
+/// to a keepalive ping in time.
+pub fn is_pong_timeout(frame: &CloseFrame<'_>) -> bool {
+    frame.code == CloseCode::Abnormal && frame.reason == PONG_TIMEOUT_REASON
+}
+
+pub(super) fn pong_timeout_frame() -> CloseFrame<'static> {
+    CloseFrame {
+        code: CloseCode::Abnormal,
+        reason: Cow::Borrowed(PONG_TIMEOUT_REASON),
+    }
+}
+
+pub(super) enum KeepaliveEvent {
+    /// A ping with the payload should be sent.
+    Ping(Vec<u8>),
+
+    /// The pong wasn't received in time.
+    Timeout,
+}
+
+struct PendingPing {
+    payload: Vec<u8>,
+    sent_at: Instant,
+    deadline: Pin<Box<Sleep>>,
+}
+
+/// Keepalive state of a connection.
+pub(super) struct Keepalive {
+    timeout: Duration,
+    interval: Interval,
+    pending: Option<PendingPing>,
+    counter: u64,
+}
+
+impl Keepalive {
+    pub(super) fn new(config: &KeepaliveConfig) -> Self {
+        let mut interval = interval_at(Instant::now() + config.interval, config.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            timeout: config.timeout,
+            interval,
+            pending: None,
+            counter: 0,
+        }
+    }
+
+    pub(super) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<KeepaliveEvent> {
+        if let Some(pending) = &mut self.pending {
+            if pending.deadline.poll_unpin(cx).is_ready() {
+                self.pending = None;
+                return Poll::Ready(KeepaliveEvent::Timeout);
+            }
+        }
+
+        while self.interval.poll_tick(cx).is_ready() {
+            // Skip the tick if the previous ping is still awaiting the pong.
+            if self.pending.is_none() {
+                self.counter += 1;
+
+                let payload = self.counter.to_be_bytes().to_vec();
+
+                self.pending = Some(PendingPing {
+                    payload: payload.clone(),
+                    sent_at: Instant::now(),
+                    deadline: Box::pin(tokio::time::sleep(self.timeout)),
+                });
+
+                return Poll::Ready(KeepaliveEvent::Ping(payload));
+            }
+        }
+
+        Poll::Pending
+    }
+
+    /// Handles the pong, returning the round-trip time if it matches the
+    /// pending ping.
+    pub(super) fn pong(&mut self, payload: &[u8]) -> Option<Duration> {
+        match &self.pending {
+            Some(pending) if pending.payload == payload => {
+                let latency = pending.sent_at.elapsed();
+                self.pending = None;
+                Some(latency)
+            }
+
+            _ => None,
+        }
+    }
+}
@@ -1,22 +1,31 @@
 use {
     crate::Error,
+    futures_util::{future::BoxFuture, FutureExt},
     pin_project::pin_project,
-    relay_rpc::rpc::{Params, RequestPayload},
+    relay_rpc::{
+        domain::MessageId,
+        rpc::{Params, RequestPayload},
+    },
     std::{
         future::Future,
         marker::PhantomData,
         pin::Pin,
         task::{ready, Context, Poll},
+        time::Duration,
     },
-    tokio::sync::oneshot,
+    tokio::{sync::oneshot, time::Sleep},
 };
 
 /// An outbound request wrapper created by [`create_request()`]. Intended be
-/// used with [`ClientStream`][crate::client::ClientStream].
+/// used with [`ClientStream`](super::ClientStream).
 #[derive(Debug)]
 pub struct OutboundRequest {
     pub(super) params: Params,
     pub(super) tx: oneshot::Sender<Result<serde_json::Value, Error>>,
+    pub(super) id: Option<MessageId>,
+    /// Resolves once the response is no longer awaited, if the request can be
+    /// cancelled.
+    pub(super) cancelled: Option<oneshot::Receiver<()>>,
 }
 
 impl OutboundRequest {
@@ -24,26 +33,68 @@ impl OutboundRequest {
         params: Params,
         tx: oneshot::Sender<Result<serde_json::Value, Error>>,
     ) -> Self {
-        Self { params, tx }
+        Self {
+            params,
+            tx,
+            id: None,
+            cancelled: None,
+        }
+    }
+
+    /// Sends the request with the provided ID instead of a newly generated
+    /// one, e.g. when retrying it.
+    pub(super) fn with_id(mut self, id: MessageId) -> Self {
+        self.id = Some(id);
+        self
     }
 }
 
 /// Future that resolves with the RPC response for the specified request.
+///
+/// Dropping the future cancels waiting for the response, and the pending
+/// request is removed from the [`ClientStream`](super::ClientStream). If the
+/// request is still waiting for the queue capacity (see
+/// [`OverflowPolicy::Wait`](super::OverflowPolicy::Wait)), it's not sent at
+/// all.
 #[must_use = "futures do nothing unless you `.await` or poll them"]
 #[pin_project]
 pub struct ResponseFuture<T> {
     #[pin]
     rx: oneshot::Receiver<Result<serde_json::Value, Error>>,
+    cancel_tx: Option<oneshot::Sender<()>>,
+    send: Option<BoxFuture<'static, ()>>,
+    timeout: Option<Pin<Box<Sleep>>>,
     _marker: PhantomData<T>,
 }
 
 impl<T> ResponseFuture<T> {
-    pub(super) fn new(rx: oneshot::Receiver<Result<serde_json::Value, Error>>) -> Self {
+    pub(super) fn new(
+        rx: oneshot::Receiver<Result<serde_json::Value, Error>>,
+        cancel_tx: oneshot::Sender<()>,
+    ) -> Self {
         Self {
             rx,
+            cancel_tx: Some(cancel_tx),
+            send: None,
+            timeout: None,
             _marker: PhantomData,
         }
     }
+
+    /// Sets the future sending the request, which is polled before waiting
+    /// for the response.
+    pub(super) fn with_send(mut self, send: BoxFuture<'static, ()>) -> Self {
+        self.send = Some(send);
+        self
+    }
+
+    /// Resolves with [`Error::Timeout`] if the response is not received within
+    /// the timeout, starting from now. Overrides the default request timeout,
+    /// if any.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(Box::pin(tokio::time::sleep(timeout)));
+        self
+    }
 }
 
 impl<T> Future for ResponseFuture<T>
@@ -53,9 +104,38 @@ where
     type Output = Result<T::Response, Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let this = self.project();
+        let mut this = self.project();
+
+        if this
+            .send
+            .as_mut()
+            .is_some_and(|send| send.poll_unpin(cx).is_ready())
+        {
+            *this.send = None;
+        }
 
-        let result = ready!(this.rx.poll(cx)).map_err(|_| Error::ChannelClosed)?;
+        let poll = if this.send.is_some() {
+            Poll::Pending
+        } else {
+            this.rx.as_mut().poll(cx)
+        };
+
+        let result = match poll {
+            Poll::Ready(result) => result.map_err(|_| Error::ChannelClosed)?,
+
+            Poll::Pending => {
+                ready!(this
+                    .timeout
+                    .as_mut()
+                    .map_or(Poll::Pending, |timeout| timeout.as_mut().poll(cx)));
+
+                // Let the stream know that the response is no longer awaited.
+                this.rx.close();
+                *this.cancel_tx = None;
+
+                return Poll::Ready(Err(Error::Timeout));
+            }
+        };
 
         let result = match result {
             Ok(value) => serde_json::from_value(value).map_err(Error::Deserialization),
@@ -80,6 +160,13 @@ impl<T> EmptyResponseFuture<T> {
     pub(super) fn new(rx: ResponseFuture<T>) -> Self {
         Self { rx }
     }
+
+    /// See [`ResponseFuture::with_timeout()`].
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self {
+            rx: self.rx.with_timeout(timeout),
+        }
+    }
 }
 
 impl<T> Future for EmptyResponseFuture<T>
@@ -95,15 +182,18 @@ where
 
 /// Creates an RPC request and returns a tuple of the request and a response
 /// future. The request is intended to be used with
-/// [`ClientStream`][crate::client::ClientStream].
+/// [`ClientStream`](super::ClientStream).
 pub fn create_request<T>(data: T) -> (OutboundRequest, ResponseFuture<T>)
 where
     T: RequestPayload,
 {
     let (tx, rx) = oneshot::channel();
+    let (cancel_tx, cancelled) = oneshot::channel();
+
+    let request = OutboundRequest {
+        cancelled: Some(cancelled),
+        ..OutboundRequest::new(data.into_params(), tx)
+    };
 
-    (
-        OutboundRequest::new(data.into_params(), tx),
-        ResponseFuture::new(rx),
-    )
+    (request, ResponseFuture::new(rx, cancel_tx))
 }
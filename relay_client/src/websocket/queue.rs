@@ -0,0 +1,286 @@
+use {
+    super::{outbound::OutboundRequest, WebsocketClientError},
+    futures_util::FutureExt,
+    std::{collections::VecDeque, pin::Pin, task::Context, time::Duration},
+    tokio::time::{Instant, Sleep},
+};
+
+/// Configuration of the queue buffering the outbound requests while the
+/// websocket client is not connected, e.g. during a reconnection.
+///
+/// The queue is enabled by the first
+/// [`Client::connect()`](super::Client::connect) call, so the requests made
+/// before that still fail with [`WebsocketClientError::NotConnected`]. From
+/// then on, the requests are queued whenever the client is not connected,
+/// including after [`Client::disconnect()`](super::Client::disconnect) and
+/// failed connection attempts, and are kept until they're sent on the next
+/// connection, expire, or the client is dropped.
+#[derive(Debug, Clone)]
+pub struct OfflineQueueConfig {
+    /// Maximum number of the queued requests. Requests exceeding it fail with
+    /// [`WebsocketClientError::OfflineQueueFull`].
+    pub capacity: usize,
+
+    /// Time after which the queued requests fail with
+    /// [`WebsocketClientError::OfflineQueueExpired`]. Changing it applies to
+    /// the requests queued from then on.
+    pub ttl: Duration,
+}
+
+impl Default for OfflineQueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 100,
+            ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+impl OfflineQueueConfig {
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+pub(super) struct OfflineQueue {
+    config: OfflineQueueConfig,
+    requests: VecDeque<(Instant, OutboundRequest)>,
+    expiry: Option<Pin<Box<Sleep>>>,
+}
+
+impl OfflineQueue {
+    pub(super) fn new(config: &OfflineQueueConfig) -> Self {
+        Self {
+            config: config.clone(),
+            requests: VecDeque::new(),
+            expiry: None,
+        }
+    }
+
+    /// Updates the configuration, which applies to the requests queued from now
+    /// on.
+    pub(super) fn set_config(&mut self, config: &OfflineQueueConfig) {
+        self.config = config.clone();
+    }
+
+    pub(super) fn push(&mut self, request: OutboundRequest) {
+        if self.requests.len() >= self.config.capacity {
+            request
+                .tx
+                .send(Err(WebsocketClientError::OfflineQueueFull.into()))
+                .ok();
+
+            return;
+        }
+
+        let deadline = Instant::now() + self.config.ttl;
+
+        // The TTL may have been shortened by the new configuration, so the request
+        // can expire ahead of the queued ones.
+        if self
+            .expiry
+            .as_ref()
+            .is_none_or(|expiry| deadline < expiry.deadline())
+        {
+            self.expiry = Some(Box::pin(tokio::time::sleep_until(deadline)));
+        }
+
+        self.requests.push_back((deadline, request));
+    }
+
+    /// Removes the oldest queued request.
+    pub(super) fn pop(&mut self) -> Option<OutboundRequest> {
+        let (_, request) = self.requests.pop_front()?;
+
+        if self.requests.is_empty() {
+            self.expiry = None;
+        }
+
+        Some(request)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Fails the expired requests. Each request keeps the deadline of the TTL
+    /// configured when it was queued, so they don't necessarily expire in
+    /// order.
+    pub(super) fn poll_expired(&mut self, cx: &mut Context<'_>) {
+        while let Some(expiry) = &mut self.expiry {
+            if expiry.poll_unpin(cx).is_pending() {
+                return;
+            }
+
+            let now = Instant::now();
+
+            let (expired, requests): (VecDeque<_>, _) = std::mem::take(&mut self.requests)
+                .into_iter()
+                .partition(|(deadline, _)| *deadline <= now);
+
+            self.requests = requests;
+
+            for (_, request) in expired {
+                request
+                    .tx
+                    .send(Err(WebsocketClientError::OfflineQueueExpired.into()))
+                    .ok();
+            }
+
+            self.expiry = self
+                .requests
+                .iter()
+                .map(|(deadline, _)| *deadline)
+                .min()
+                .map(|deadline| Box::pin(tokio::time::sleep_until(deadline)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            websocket::{create_request, ResponseFuture},
+            Error,
+        },
+        futures_util::future::poll_fn,
+        relay_rpc::{
+            domain::Topic,
+            rpc::{Params, Subscribe},
+        },
+        std::task::Poll,
+    };
+
+    fn request(topic: &Topic) -> (OutboundRequest, ResponseFuture<Subscribe>) {
+        create_request(Subscribe {
+            topic: topic.clone(),
+        })
+    }
+
+    async fn poll_expired(queue: &mut OfflineQueue) {
+        poll_fn(|cx| {
+            queue.poll_expired(cx);
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn order() {
+        let mut queue = OfflineQueue::new(&OfflineQueueConfig::default());
+        let topics = [Topic::generate(), Topic::generate(), Topic::generate()];
+
+        for topic in &topics {
+            queue.push(request(topic).0);
+        }
+
+        assert_eq!(queue.len(), 3);
+
+        for topic in &topics {
+            assert_eq!(
+                queue.pop().unwrap().params,
+                Params::Subscribe(Subscribe {
+                    topic: topic.clone()
+                })
+            );
+        }
+
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn overflow() {
+        let mut queue = OfflineQueue::new(&OfflineQueueConfig::default().with_capacity(2));
+        let mut responses = Vec::new();
+
+        for _ in 0..3 {
+            let (request, response) = request(&Topic::generate());
+            queue.push(request);
+            responses.push(response);
+        }
+
+        assert_eq!(queue.len(), 2);
+        assert!(matches!(
+            responses.pop().unwrap().await,
+            Err(Error::WebsocketClient(
+                WebsocketClientError::OfflineQueueFull
+            ))
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expiry() {
+        let config = OfflineQueueConfig::default().with_ttl(Duration::from_secs(10));
+        let mut queue = OfflineQueue::new(&config);
+
+        let (request1, response1) = request(&Topic::generate());
+        queue.push(request1);
+
+        tokio::time::advance(Duration::from_secs(5)).await;
+
+        let topic2 = Topic::generate();
+        let (request2, _response2) = request(&topic2);
+        queue.push(request2);
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        poll_expired(&mut queue).await;
+
+        assert!(matches!(
+            response1.await,
+            Err(Error::WebsocketClient(
+                WebsocketClientError::OfflineQueueExpired
+            ))
+        ));
+        assert_eq!(
+            queue.pop().unwrap().params,
+            Params::Subscribe(Subscribe { topic: topic2 })
+        );
+
+        tokio::time::advance(Duration::from_secs(10)).await;
+        poll_expired(&mut queue).await;
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shortened_ttl() {
+        let config = OfflineQueueConfig::default().with_ttl(Duration::from_secs(10));
+        let mut queue = OfflineQueue::new(&config);
+
+        let topic1 = Topic::generate();
+        let (request1, _response1) = request(&topic1);
+        queue.push(request1);
+
+        // The request queued with the shorter TTL expires first.
+        queue.set_config(&config.with_ttl(Duration::from_secs(1)));
+
+        let (request2, response2) = request(&Topic::generate());
+        queue.push(request2);
+
+        tokio::time::advance(Duration::from_secs(2)).await;
+        poll_expired(&mut queue).await;
+
+        assert!(matches!(
+            response2.await,
+            Err(Error::WebsocketClient(
+                WebsocketClientError::OfflineQueueExpired
+            ))
+        ));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(
+            queue.pop().unwrap().params,
+            Params::Subscribe(Subscribe { topic: topic1 })
+        );
+    }
+}
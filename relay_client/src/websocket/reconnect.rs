@@ -0,0 +1,161 @@
+use {
+    super::CloseFrame,
+    relay_rpc::auth::rand,
+    std::time::Duration,
+    tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode,
+};
+
+/// Policy for reconnecting automatically after the Relay connection is
+/// closed by the remote side or by a transport error.
+///
+/// The delay before each attempt grows exponentially from
+/// [`initial_delay`](Self::initial_delay) up to
+/// [`max_delay`](Self::max_delay), and is randomly reduced by up to
+/// [`jitter`](Self::jitter) to spread out the reconnecting clients.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt.
+    pub initial_delay: Duration,
+
+    /// Maximum delay between the attempts.
+    pub max_delay: Duration,
+
+    /// Factor by which the delay grows after each failed attempt.
+    pub multiplier: f64,
+
+    /// Fraction of the delay, between `0.0` and `1.0`, that is randomized.
+    pub jitter: f64,
+
+    /// Maximum number of attempts, or `None` to keep trying forever.
+    pub max_attempts: Option<u32>,
+
+    /// Whether to reconnect if the connection is closed without a close frame,
+    /// e.g. due to a transport error.
+    pub on_transport_error: bool,
+
+    /// Close codes that trigger a reconnect.
+    pub close_codes: Vec<CloseCode>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_attempts: None,
+            on_transport_error: true,
+            close_codes: vec![
+                CloseCode::Away,
+                CloseCode::Abnormal,
+                CloseCode::Error,
+                CloseCode::Restart,
+                CloseCode::Again,
+            ],
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    pub fn with_initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_max_attempts(mut self, max_attempts: impl Into<Option<u32>>) -> Self {
+        self.max_attempts = max_attempts.into();
+        self
+    }
+
+    pub fn with_transport_errors(mut self, reconnect: bool) -> Self {
+        self.on_transport_error = reconnect;
+        self
+    }
+
+    pub fn with_close_codes(mut self, close_codes: impl Into<Vec<CloseCode>>) -> Self {
+        self.close_codes = close_codes.into();
+        self
+    }
+
+    /// Returns whether the connection closed with the frame should be
+    /// reconnected.
+    pub fn should_reconnect(&self, frame: Option<&CloseFrame<'_>>) -> bool {
+        match frame {
+            Some(frame) => self.close_codes.contains(&frame.code),
+            None => self.on_transport_error,
+        }
+    }
+
+    /// Returns whether another attempt is allowed after the number of failed
+    /// attempts.
+    pub fn can_retry(&self, attempts: u32) -> bool {
+        self.max_attempts.is_none_or(|max| attempts < max)
+    }
+
+    /// Returns the delay before the attempt, starting at `1`, without jitter.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+
+    /// Returns the delay before the attempt, starting at `1`, with jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0) * rand::random::<f64>();
+
+        self.base_delay(attempt).mul_f64(1.0 - jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::borrow::Cow};
+
+    #[test]
+    fn backoff() {
+        let policy = ReconnectPolicy::default()
+            .with_initial_delay(Duration::from_secs(1))
+            .with_max_delay(Duration::from_secs(10))
+            .with_max_attempts(5);
+
+        let delays = (1..=6)
+            .map(|attempt| policy.base_delay(attempt).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+
+        for attempt in 1..=6 {
+            let delay = policy.delay(attempt);
+            assert!(delay <= policy.base_delay(attempt));
+            assert!(delay >= policy.base_delay(attempt).mul_f64(0.5));
+        }
+
+        assert!(policy.can_retry(4));
+        assert!(!policy.can_retry(5));
+
+        let frame = |code| CloseFrame {
+            code,
+            reason: Cow::Borrowed(""),
+        };
+        assert!(policy.should_reconnect(None));
+        assert!(policy.should_reconnect(Some(&frame(CloseCode::Restart))));
+        assert!(!policy.should_reconnect(Some(&frame(CloseCode::Normal))));
+        assert!(!policy.with_transport_errors(false).should_reconnect(None));
+    }
+}
@@ -0,0 +1,105 @@
+use {
+    super::{ConnectionState, WebsocketClientError},
+    crate::Error,
+    std::time::Duration,
+    tokio::sync::watch,
+};
+
+/// Policy for retrying the publish requests with
+/// [`Client::publish_with_retry()`](super::Client::publish_with_retry) when
+/// the connection is lost before the Relay responds.
+///
+/// All attempts are sent with the same message ID and the same message, and
+/// therefore the same message hash (see
+/// [`get_message_id()`](relay_rpc::rpc::msg_id::get_message_id)), so that the
+/// Relay can deduplicate the message if an earlier attempt went out.
+#[derive(Debug, Clone)]
+pub struct PublishRetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// Optional time to wait for the connection to be re-established before
+    /// each retry. Disabled by default, i.e. waits as long as the
+    /// [`ReconnectPolicy`](super::ReconnectPolicy) keeps reconnecting.
+    pub reconnect_timeout: Option<Duration>,
+}
+
+impl Default for PublishRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            reconnect_timeout: None,
+        }
+    }
+}
+
+impl PublishRetryPolicy {
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn with_reconnect_timeout(mut self, timeout: impl Into<Option<Duration>>) -> Self {
+        self.reconnect_timeout = timeout.into();
+        self
+    }
+
+    /// Returns whether the request should be retried after the attempt failed
+    /// with the error, i.e. whether the Relay may not have received it.
+    pub(super) fn should_retry(&self, attempt: u32, error: &Error) -> bool {
+        attempt < self.max_attempts
+            && matches!(
+                error,
+                Error::Timeout
+                    | Error::WebsocketClient(
+                        WebsocketClientError::ConnectionClosed(_)
+                            | WebsocketClientError::NotConnected
+                            | WebsocketClientError::Transport(_)
+                    )
+            )
+    }
+
+    /// Waits until the request can be retried. Returns `false` if the client
+    /// is disconnected for good, or hasn't reconnected in time.
+    ///
+    /// The state must be marked as seen when the failed attempt was sent, as
+    /// the attempt may fail before the state is updated.
+    pub(super) async fn wait_retry(
+        &self,
+        error: &Error,
+        state: &mut watch::Receiver<ConnectionState>,
+    ) -> bool {
+        // A timed out request is retried on the same connection.
+        if matches!(error, Error::Timeout) && state.borrow().is_connected() {
+            return true;
+        }
+
+        let reconnected = wait_reconnected(state);
+
+        match self.reconnect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, reconnected)
+                .await
+                .unwrap_or(false),
+
+            None => reconnected.await,
+        }
+    }
+}
+
+async fn wait_reconnected(state: &mut watch::Receiver<ConnectionState>) -> bool {
+    let mut changed = state.has_changed().unwrap_or(false);
+
+    loop {
+        match *state.borrow_and_update() {
+            ConnectionState::Connected if changed => return true,
+            ConnectionState::Disconnected => return false,
+            _ => {}
+        }
+
+        if state.changed().await.is_err() {
+            return false;
+        }
+
+        changed = true;
+    }
+}
@@ -0,0 +1,26 @@
+/// State of the Relay connection, observed with
+/// [`Client::state()`](super::Client::state).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Not connected, either before the first
+    /// [`Client::connect()`](super::Client::connect) call, after
+    /// disconnecting, or after the automatic reconnection has given up.
+    #[default]
+    Disconnected,
+
+    /// Connecting with [`Client::connect()`](super::Client::connect).
+    Connecting,
+
+    /// Connected to the Relay.
+    Connected,
+
+    /// Waiting for, or making an automatic reconnection attempt (see
+    /// [`ReconnectPolicy`](super::ReconnectPolicy)).
+    Reconnecting,
+}
+
+impl ConnectionState {
+    pub fn is_connected(&self) -> bool {
+        *self == Self::Connected
+    }
+}
@@ -1,33 +1,45 @@
 use {
     super::{
+        backpressure::QueueConfig,
+        config::WebsocketConfig,
         inbound::InboundRequest,
+        keepalive::{pong_timeout_frame, Keepalive, KeepaliveConfig, KeepaliveEvent},
         outbound::{create_request, OutboundRequest, ResponseFuture},
         CloseReason,
         TransportError,
         WebsocketClientError,
     },
     crate::{error::Error, HttpRequest, MessageIdGenerator},
-    futures_util::{stream::FusedStream, SinkExt, Stream, StreamExt},
+    futures_util::{
+        future::BoxFuture,
+        stream::{FusedStream, FuturesUnordered},
+        FutureExt,
+        Sink,
+        SinkExt,
+        Stream,
+        StreamExt,
+    },
     relay_rpc::{
         domain::MessageId,
         rpc::{Params, Payload, Request, RequestPayload, Response, Subscription},
     },
     std::{
-        collections::{hash_map::Entry, HashMap},
+        collections::HashMap,
         pin::Pin,
         task::{Context, Poll},
+        time::Duration,
     },
     tokio::{
         net::TcpStream,
         sync::{
             mpsc,
-            mpsc::{UnboundedReceiver, UnboundedSender},
+            mpsc::{Receiver, Sender},
             oneshot,
         },
     },
     tokio_tungstenite::{
-        connect_async,
-        tungstenite::{protocol::CloseFrame, Message},
+        tungstenite::{error::CapacityError, protocol::CloseFrame, Message},
+        Connector,
         MaybeTlsStream,
         WebSocketStream,
     },
@@ -35,12 +47,77 @@ use {
 
 pub type SocketStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
+/// Establishes the websocket connection over the TCP stream, upgrading it to
+/// TLS with the connector, or the default one of the TLS backend, for the `wss`
+/// addresses.
+pub(super) async fn handshake(
+    request: HttpRequest<()>,
+    stream: TcpStream,
+    config: &WebsocketConfig,
+    connector: Option<Connector>,
+) -> Result<SocketStream, TransportError> {
+    let config = Some(config.as_tungstenite());
+
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    let (socket, _) =
+        tokio_tungstenite::client_async_tls_with_config(request, stream, config, connector).await?;
+
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    let (socket, _) = {
+        use tokio_tungstenite::tungstenite::error::UrlError;
+
+        if connector.is_some() || request.uri().scheme_str() == Some("wss") {
+            return Err(TransportError::Url(UrlError::TlsFeatureNotEnabled));
+        }
+
+        tokio_tungstenite::client_async_with_config(request, MaybeTlsStream::Plain(stream), config)
+            .await?
+    };
+
+    Ok(socket)
+}
+
 /// Opens a connection to the Relay and returns [`ClientStream`] for the
 /// connection.
 pub async fn create_stream(request: HttpRequest<()>) -> Result<ClientStream, WebsocketClientError> {
-    let (socket, _) = connect_async(request)
-        .await
-        .map_err(WebsocketClientError::ConnectionFailed)?;
+    create_stream_with_config(request, &WebsocketConfig::default()).await
+}
+
+/// Same as [`create_stream()`], with the websocket limits and socket options.
+pub async fn create_stream_with_config(
+    request: HttpRequest<()>,
+    config: &WebsocketConfig,
+) -> Result<ClientStream, WebsocketClientError> {
+    let connect = async {
+        let uri = request.uri();
+        let host = uri
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = uri
+            .port_u16()
+            .unwrap_or(if uri.scheme_str() == Some("wss") {
+                443
+            } else {
+                80
+            });
+
+        let stream = TcpStream::connect((host, port)).await?;
+        config.configure_socket(&stream)?;
+
+        handshake(request, stream, config, None).await
+    };
+
+    let result = match config.connect_timeout {
+        Some(timeout) => tokio::time::timeout(timeout, connect)
+            .await
+            .map_err(|_| WebsocketClientError::ConnectTimeout)?,
+
+        None => connect.await,
+    };
+
+    let socket = result.map_err(WebsocketClientError::ConnectionFailed)?;
 
     Ok(ClientStream::new(socket))
 }
@@ -64,8 +141,16 @@ pub enum StreamEvent {
     /// stream.
     OutboundError(Error),
 
+    /// Pong received in response to a keepalive ping (see
+    /// [`ClientStream::with_keepalive()`]), with the round-trip time.
+    Pong(Duration),
+
     /// The websocket connection was closed.
     ///
+    /// If the Relay doesn't respond to a keepalive ping in time, the
+    /// connection is closed with the frame recognized by
+    /// [`is_pong_timeout()`](super::is_pong_timeout).
+    ///
     /// This is the last event that can be produced by the stream.
     ConnectionClosed(Option<CloseFrame<'static>>),
 }
@@ -75,52 +160,144 @@ pub enum StreamEvent {
 /// The stream produces [`StreamEvent`] when polled, and can be used to send RPC
 /// requests (see [`ClientStream::send()`] and [`ClientStream::send_raw()`]).
 ///
-/// For a higher-level interface see [`Client`](crate::client::Client). For an
+/// The connection is a websocket over TCP by default, but the stream can run
+/// over any [`Sink`] and [`Stream`] of the websocket messages (see
+/// [`Transport`](super::Transport)).
+///
+/// For a higher-level interface see [`Client`](super::Client). For an
 /// example usage of the stream see `client::connection` module.
-pub struct ClientStream {
-    socket: SocketStream,
-    outbound_tx: UnboundedSender<Message>,
-    outbound_rx: UnboundedReceiver<Message>,
+pub struct ClientStream<S = SocketStream> {
+    socket: S,
+    outbound_tx: Sender<Message>,
+    outbound_rx: Receiver<Message>,
+    outbound_capacity: usize,
+    response_tx: Sender<Message>,
+    response_rx: Receiver<Message>,
+    response_capacity: usize,
+    ping: Option<Message>,
     requests: HashMap<MessageId, oneshot::Sender<Result<serde_json::Value, Error>>>,
+    cancellations: FuturesUnordered<BoxFuture<'static, MessageId>>,
     id_generator: MessageIdGenerator,
     close_frame: Option<CloseFrame<'static>>,
+    keepalive: Option<Keepalive>,
+    timed_out: bool,
+    closed: bool,
+    read_paused: bool,
 }
 
-impl ClientStream {
-    pub fn new(socket: SocketStream) -> Self {
+impl<S> ClientStream<S>
+where
+    S: Sink<Message, Error = TransportError>
+        + Stream<Item = Result<Message, TransportError>>
+        + Unpin,
+{
+    pub fn new(socket: S) -> Self {
+        let config = QueueConfig::default();
         let requests = HashMap::new();
-        let (outbound_tx, outbound_rx) = mpsc::unbounded_channel();
+        let (outbound_tx, outbound_rx) = mpsc::channel(config.outbound_capacity());
+        let (response_tx, response_rx) = mpsc::channel(config.response_capacity());
         let id_generator = MessageIdGenerator::new();
 
         Self {
             socket,
             outbound_tx,
             outbound_rx,
+            outbound_capacity: config.outbound_capacity(),
+            response_tx,
+            response_rx,
+            response_capacity: config.response_capacity(),
+            ping: None,
             requests,
+            cancellations: FuturesUnordered::new(),
             id_generator,
             close_frame: None,
+            keepalive: None,
+            timed_out: false,
+            closed: false,
+            read_paused: false,
         }
     }
 
+    /// Enables the keepalive pings with the provided configuration, or
+    /// disables them if `None`.
+    pub fn with_keepalive(mut self, config: Option<&KeepaliveConfig>) -> Self {
+        self.keepalive = config.map(Keepalive::new);
+        self
+    }
+
+    /// Sets the capacities of the outbound and response queues. Must be called
+    /// before sending any requests.
+    pub fn with_queues(mut self, config: &QueueConfig) -> Self {
+        (self.outbound_tx, self.outbound_rx) = mpsc::channel(config.outbound_capacity());
+        (self.response_tx, self.response_rx) = mpsc::channel(config.response_capacity());
+        self.outbound_capacity = config.outbound_capacity();
+        self.response_capacity = config.response_capacity();
+        self
+    }
+
+    /// Returns whether the outbound queue has capacity for more requests.
+    /// Requests sent while it's full fail with
+    /// [`WebsocketClientError::QueueFull`].
+    pub fn has_capacity(&self) -> bool {
+        self.outbound_tx.capacity() > 0
+    }
+
+    /// Returns the number of the messages waiting to be written to the
+    /// websocket.
+    pub fn outbound_len(&self) -> usize {
+        self.outbound_capacity - self.outbound_tx.capacity()
+    }
+
+    /// Returns the number of the responses to the inbound requests waiting to
+    /// be written to the websocket.
+    pub fn responses_len(&self) -> usize {
+        self.response_capacity - self.response_tx.capacity()
+    }
+
+    /// Sets the generator of the request IDs, e.g. to share it with the
+    /// requests that are sent with a fixed ID.
+    pub fn with_id_generator(mut self, id_generator: MessageIdGenerator) -> Self {
+        self.id_generator = id_generator;
+        self
+    }
+
+    /// Pauses or resumes reading from the websocket, e.g. while the inbound
+    /// messages can't be handled. Writing continues meanwhile.
+    pub(super) fn pause_reading(&mut self, paused: bool) {
+        self.read_paused = paused;
+    }
+
     /// Sends an already serialized [`OutboundRequest`][OutboundRequest] (see
     /// [`create_request()`]).
     pub fn send_raw(&mut self, request: OutboundRequest) {
         let tx = request.tx;
-        let id = self.id_generator.next();
+        let cancelled = request.cancelled;
+        let id = request.id.unwrap_or_else(|| self.id_generator.next());
         let request = Payload::Request(Request::new(id, request.params));
         let serialized = serde_json::to_string(&request);
 
         match serialized {
-            Ok(data) => match self.requests.entry(id) {
-                Entry::Occupied(_) => {
+            Ok(data) => {
+                // A retried request may reuse the ID of an abandoned one, e.g. after
+                // a timeout.
+                if self.requests.get(&id).is_some_and(|tx| !tx.is_closed()) {
                     tx.send(Err(Error::DuplicateRequestId)).ok();
+                } else if self.outbound_tx.try_send(Message::Text(data)).is_ok() {
+                    self.requests.insert(id, tx);
+
+                    if let Some(cancelled) = cancelled {
+                        self.cancellations.push(
+                            async move {
+                                cancelled.await.ok();
+                                id
+                            }
+                            .boxed(),
+                        );
+                    }
+                } else {
+                    tx.send(Err(WebsocketClientError::QueueFull.into())).ok();
                 }
-
-                Entry::Vacant(entry) => {
-                    entry.insert(tx);
-                    self.outbound_tx.send(Message::Text(data)).ok();
-                }
-            },
+            }
 
             Err(err) => {
                 tx.send(Err(Error::Serialization(err))).ok();
@@ -128,6 +305,17 @@ impl ClientStream {
         }
     }
 
+    /// Removes the pending requests whose responses are no longer awaited, i.e.
+    /// the [`ResponseFuture`] has been dropped or has timed out.
+    fn remove_cancelled(&mut self, cx: &mut Context<'_>) {
+        while let Poll::Ready(Some(id)) = self.cancellations.poll_next_unpin(cx) {
+            // The ID may have been reused by a retried request since.
+            if self.requests.get(&id).is_some_and(|tx| tx.is_closed()) {
+                self.requests.remove(&id);
+            }
+        }
+    }
+
     /// Serialize the request into a generic [`OutboundRequest`] and sends it,
     /// returning a future that resolves with the response.
     pub fn send<T>(&mut self, request: T) -> ResponseFuture<T>
@@ -143,7 +331,7 @@ impl ClientStream {
     pub async fn close(&mut self, frame: Option<CloseFrame<'static>>) -> Result<(), Error> {
         self.close_frame = frame.clone();
         self.socket
-            .close(frame)
+            .send(Message::Close(frame))
             .await
             .map_err(|err| WebsocketClientError::ClosingFailed(err).into())
     }
@@ -168,7 +356,7 @@ impl ClientStream {
                                 match request.params {
                                     Params::Subscription(data) => {
                                         StreamEvent::InboundSubscriptionRequest(
-                                            InboundRequest::new(id, data, self.outbound_tx.clone()),
+                                            InboundRequest::new(id, data, self.response_tx.clone()),
                                         )
                                     }
 
@@ -221,6 +409,12 @@ impl ClientStream {
                     }
                 }
 
+                Message::Pong(payload) => self
+                    .keepalive
+                    .as_mut()
+                    .and_then(|keepalive| keepalive.pong(payload))
+                    .map(StreamEvent::Pong),
+
                 Message::Close(frame) => {
                     self.close_frame = frame.clone();
                     Some(StreamEvent::ConnectionClosed(frame.clone()))
@@ -229,9 +423,21 @@ impl ClientStream {
                 _ => None,
             },
 
-            Err(error) => Some(StreamEvent::InboundError(
-                WebsocketClientError::Transport(error).into(),
-            )),
+            Err(error) => {
+                // The transport errors are fatal, as with the websocket streams, so the
+                // connection is reported as closed after this.
+                self.closed = true;
+
+                let error = match error {
+                    TransportError::Capacity(CapacityError::MessageTooLong { size, max_size }) => {
+                        WebsocketClientError::MessageTooLarge { size, max_size }
+                    }
+
+                    error => WebsocketClientError::Transport(error),
+                };
+
+                Some(StreamEvent::InboundError(error.into()))
+            }
         }
     }
 
@@ -244,7 +450,7 @@ impl ClientStream {
             match self.socket.poll_ready_unpin(cx) {
                 // The sink is ready to accept more data.
                 Poll::Ready(Ok(())) => {
-                    if let Poll::Ready(Some(next_message)) = self.outbound_rx.poll_recv(cx) {
+                    if let Some(next_message) = self.poll_next_message(cx) {
                         if let Err(err) = self.socket.start_send_unpin(next_message) {
                             return Poll::Ready(Err(err));
                         }
@@ -265,29 +471,92 @@ impl ClientStream {
             }
         }
     }
+
+    fn poll_next_message(&mut self, cx: &mut Context<'_>) -> Option<Message> {
+        if let Some(ping) = self.ping.take() {
+            return Some(ping);
+        }
+
+        // The responses go first, since reading from the websocket is paused while
+        // their queue is full.
+        if let Poll::Ready(message) = self.response_rx.poll_recv(cx) {
+            return message;
+        }
+
+        match self.outbound_rx.poll_recv(cx) {
+            Poll::Ready(message) => message,
+            Poll::Pending => None,
+        }
+    }
 }
 
-impl Stream for ClientStream {
+impl<S> Stream for ClientStream<S>
+where
+    S: Sink<Message, Error = TransportError>
+        + Stream<Item = Result<Message, TransportError>>
+        + Unpin,
+{
     type Item = StreamEvent;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        if self.socket.is_terminated() {
+        if self.is_terminated() {
             return Poll::Ready(None);
         }
 
-        while let Poll::Ready(data) = self.socket.poll_next_unpin(cx) {
-            match data {
-                Some(result) => {
+        self.remove_cancelled(cx);
+
+        while let Some(Poll::Ready(event)) =
+            self.keepalive.as_mut().map(|keepalive| keepalive.poll(cx))
+        {
+            match event {
+                KeepaliveEvent::Ping(payload) => {
+                    // Pings bypass the outbound queue, so that a full queue doesn't
+                    // delay them until the pong timeout.
+                    self.ping = Some(Message::Ping(payload));
+                }
+
+                KeepaliveEvent::Timeout => {
+                    // The connection is presumably dead, so there's no point in
+                    // waiting for the closing handshake.
+                    self.timed_out = true;
+                    self.close_frame = Some(pong_timeout_frame());
+
+                    return Poll::Ready(Some(StreamEvent::ConnectionClosed(
+                        self.close_frame.clone(),
+                    )));
+                }
+            }
+        }
+
+        // Stop reading while the responses to the inbound requests can't be queued.
+        let mut paused = false;
+
+        loop {
+            if self.read_paused {
+                break;
+            }
+
+            if self.response_tx.capacity() == 0 {
+                paused = true;
+                break;
+            }
+
+            match self.socket.poll_next_unpin(cx) {
+                Poll::Ready(Some(result)) => {
                     if let Some(event) = self.parse_inbound(result) {
                         return Poll::Ready(Some(event));
                     }
                 }
 
-                None => {
+                Poll::Ready(None) => {
+                    self.closed = true;
+
                     return Poll::Ready(Some(StreamEvent::ConnectionClosed(
                         self.close_frame.clone(),
-                    )))
+                    )));
                 }
+
+                Poll::Pending => break,
             }
         }
 
@@ -296,18 +565,30 @@ impl Stream for ClientStream {
                 WebsocketClientError::Transport(error).into(),
             ))),
 
-            _ => Poll::Pending,
+            _ => {
+                // Resume reading once some of the responses have been written.
+                if paused && self.response_tx.capacity() > 0 {
+                    cx.waker().wake_by_ref();
+                }
+
+                Poll::Pending
+            }
         }
     }
 }
 
-impl FusedStream for ClientStream {
+impl<S> FusedStream for ClientStream<S>
+where
+    S: Sink<Message, Error = TransportError>
+        + Stream<Item = Result<Message, TransportError>>
+        + Unpin,
+{
     fn is_terminated(&self) -> bool {
-        self.socket.is_terminated()
+        self.timed_out || self.closed
     }
 }
 
-impl Drop for ClientStream {
+impl<S> Drop for ClientStream<S> {
     fn drop(&mut self) {
         let reason = CloseReason(self.close_frame.take());
 
@@ -319,3 +600,89 @@ impl Drop for ClientStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        futures_util::future::poll_fn,
+        relay_rpc::{domain::Topic, rpc::Subscribe},
+        tokio::io::DuplexStream,
+        tokio_tungstenite::tungstenite::protocol::Role,
+    };
+
+    async fn stream() -> (ClientStream<WebSocketStream<DuplexStream>>, DuplexStream) {
+        let (client, server) = tokio::io::duplex(4096);
+        let socket = WebSocketStream::from_raw_socket(client, Role::Client, None).await;
+
+        (ClientStream::new(socket), server)
+    }
+
+    async fn poll(stream: &mut ClientStream<WebSocketStream<DuplexStream>>) {
+        poll_fn(|cx| {
+            assert!(stream.poll_next_unpin(cx).is_pending());
+            Poll::Ready(())
+        })
+        .await
+    }
+
+    fn subscribe() -> Subscribe {
+        Subscribe {
+            topic: Topic::generate(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_requests() {
+        let (mut stream, _server) = stream().await;
+
+        let dropped = stream.send(subscribe());
+        let timed_out = stream
+            .send(subscribe())
+            .with_timeout(Duration::from_secs(1));
+        let _pending = stream.send(subscribe());
+
+        poll(&mut stream).await;
+        assert_eq!(stream.requests.len(), 3);
+
+        drop(dropped);
+        poll(&mut stream).await;
+        assert_eq!(stream.requests.len(), 2);
+
+        assert!(matches!(timed_out.await, Err(Error::Timeout)));
+        poll(&mut stream).await;
+        assert_eq!(stream.requests.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn duplicate_request_id() {
+        let (mut stream, _server) = stream().await;
+        let id = MessageId::new(1);
+
+        let (request, _pending) = create_request(subscribe());
+        stream.send_raw(request.with_id(id));
+
+        let (request, duplicate) = create_request(subscribe());
+        stream.send_raw(request.with_id(id));
+
+        assert!(matches!(duplicate.await, Err(Error::DuplicateRequestId)));
+    }
+
+    #[tokio::test]
+    async fn zero_capacity() {
+        let (stream, _server) = stream().await;
+        let config = QueueConfig::default()
+            .with_outbound_capacity(0)
+            .with_response_capacity(0);
+        let mut stream = stream.with_queues(&config);
+
+        let _sent = stream.send(subscribe());
+        assert!(!stream.has_capacity());
+        assert_eq!(stream.outbound_len(), 1);
+
+        assert!(matches!(
+            stream.send(subscribe()).await,
+            Err(Error::WebsocketClient(WebsocketClientError::QueueFull))
+        ));
+    }
+}
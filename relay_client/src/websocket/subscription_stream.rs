@@ -0,0 +1,221 @@
+use {
+    super::{create_request, Client, PublishedMessage},
+    futures_util::Stream,
+    relay_rpc::{domain::Topic, rpc::Unsubscribe},
+    std::{
+        collections::{HashMap, HashSet, VecDeque},
+        pin::Pin,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+        task::{Context, Poll},
+    },
+    tokio::sync::mpsc::{self, error::TrySendError, Receiver, Sender},
+};
+
+#[derive(Debug)]
+struct Routes {
+    capacity: usize,
+    next_id: u64,
+    topics: HashMap<Topic, Vec<(u64, Sender<PublishedMessage>)>>,
+    // Topics also subscribed with `Client::subscribe()`, which stay subscribed
+    // once their streams are dropped.
+    held: HashSet<Topic>,
+}
+
+/// Routes the messages of the topics to the [`SubscriptionStream`]s, shared
+/// between the [`Client`] and the connection event loop.
+#[derive(Debug, Clone)]
+pub(super) struct MessageRoutes(Arc<Mutex<Routes>>);
+
+impl MessageRoutes {
+    /// Creates the routes with the capacity of each stream.
+    pub(super) fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(Routes {
+            capacity,
+            next_id: 0,
+            topics: HashMap::new(),
+            held: HashSet::new(),
+        })))
+    }
+
+    /// Marks the topics as subscribed with [`Client::subscribe()`], so that
+    /// dropping their streams doesn't unsubscribe from them.
+    pub(super) fn hold<'a>(&self, topics: impl IntoIterator<Item = &'a Topic>) {
+        self.lock().held.extend(topics.into_iter().cloned());
+    }
+
+    /// Marks the topics as unsubscribed with [`Client::unsubscribe()`].
+    pub(super) fn release<'a>(&self, topics: impl IntoIterator<Item = &'a Topic>) {
+        let mut routes = self.lock();
+
+        for topic in topics {
+            routes.held.remove(topic);
+        }
+    }
+
+    fn add(&self, topic: Topic) -> (u64, Receiver<PublishedMessage>) {
+        let mut routes = self.lock();
+        let (tx, rx) = mpsc::channel(routes.capacity);
+
+        let id = routes.next_id;
+        routes.next_id += 1;
+        routes.topics.entry(topic).or_default().push((id, tx));
+
+        (id, rx)
+    }
+
+    /// Removes the route, returning whether it was the last reference to the
+    /// topic subscription, i.e. the last route of a topic that isn't held.
+    fn remove(&self, topic: &Topic, id: u64) -> bool {
+        let mut routes = self.lock();
+
+        let Some(senders) = routes.topics.get_mut(topic) else {
+            return false;
+        };
+
+        senders.retain(|(route_id, _)| *route_id != id);
+
+        if senders.is_empty() {
+            routes.topics.remove(topic);
+            !routes.held.contains(topic)
+        } else {
+            false
+        }
+    }
+
+    /// Sends the message to the streams of its topic, returning the ones that
+    /// are full along with the message. Returns the message back if there are
+    /// no streams.
+    pub(super) fn route(
+        &self,
+        message: PublishedMessage,
+    ) -> Result<RouteBacklog, PublishedMessage> {
+        let routes = self.lock();
+
+        let Some(senders) = routes.topics.get(&message.topic) else {
+            return Err(message);
+        };
+
+        let mut full = RouteBacklog::default();
+
+        for (_, tx) in senders {
+            // The stream may have been dropped, in which case the message is
+            // discarded.
+            if let Err(TrySendError::Full(message)) = tx.try_send(message.clone()) {
+                full.0.push_back((tx.clone(), message));
+            }
+        }
+
+        Ok(full)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Routes> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Messages waiting for the capacity of their [`SubscriptionStream`]s.
+#[derive(Debug, Default)]
+pub(super) struct RouteBacklog(VecDeque<(Sender<PublishedMessage>, PublishedMessage)>);
+
+impl RouteBacklog {
+    pub(super) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(super) fn append(&mut self, other: &mut Self) {
+        self.0.append(&mut other.0);
+    }
+
+    /// Waits for the stream capacity, and sends the oldest message.
+    pub(super) async fn flush(&mut self) {
+        let Some((tx, _)) = self.0.front() else {
+            return;
+        };
+
+        let tx = tx.clone();
+        let permit = tx.reserve().await;
+
+        // The stream may have been dropped meanwhile, in which case the message is
+        // discarded.
+        let Some((_, message)) = self.0.pop_front() else {
+            return;
+        };
+
+        if let Ok(permit) = permit {
+            permit.send(message);
+        }
+    }
+}
+
+/// Stream of the messages received on a topic, created by
+/// [`Client::subscribe_stream()`].
+///
+/// Dropping the stream unsubscribes from the topic, unless there are other
+/// streams for it, or it's also subscribed with [`Client::subscribe()`] or
+/// [`Client::batch_subscribe()`] and not unsubscribed since. Outside of a
+/// Tokio runtime, the unsubscribe request is only sent if the
+/// [`QueueConfig::control_capacity`](super::QueueConfig::control_capacity)
+/// allows it without waiting.
+///
+/// The received messages are queued up to the
+/// [`QueueConfig::stream_capacity`](super::QueueConfig::stream_capacity),
+/// after which the [`OverflowPolicy`](super::OverflowPolicy) applies.
+pub struct SubscriptionStream {
+    client: Client,
+    topic: Topic,
+    id: u64,
+    rx: Receiver<PublishedMessage>,
+}
+
+impl SubscriptionStream {
+    pub(super) fn new(client: Client, topic: Topic) -> Self {
+        let (id, rx) = client.routes.add(topic.clone());
+
+        Self {
+            client,
+            topic,
+            id,
+            rx,
+        }
+    }
+
+    pub fn topic(&self) -> &Topic {
+        &self.topic
+    }
+}
+
+impl Stream for SubscriptionStream {
+    type Item = PublishedMessage;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for SubscriptionStream {
+    fn drop(&mut self) {
+        if !self.client.routes.remove(&self.topic, self.id) {
+            return;
+        }
+
+        // The subscription ID may have changed after reconnecting, so the current one
+        // is taken from the registry. The topic is not there if subscribing failed.
+        if let Some(subscription_id) = self.client.subscriptions.get(&self.topic) {
+            let (request, _) = create_request(Unsubscribe {
+                topic: self.topic.clone(),
+                subscription_id,
+            });
+
+            // Waiting for the control queue capacity requires a runtime.
+            if tokio::runtime::Handle::try_current().is_ok() {
+                self.client.request(request);
+            } else {
+                self.client.try_request(request);
+            }
+        }
+    }
+}
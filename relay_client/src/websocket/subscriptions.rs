@@ -0,0 +1,189 @@
+use {
+    crate::Error,
+    futures_util::{future::BoxFuture, FutureExt},
+    relay_rpc::{
+        domain::{SubscriptionId, Topic},
+        rpc::{BatchSubscribe, Params, RequestPayload},
+    },
+    serde::Deserialize,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, MutexGuard, PoisonError},
+    },
+    tokio::sync::oneshot,
+};
+
+type ResponseSender = oneshot::Sender<Result<serde_json::Value, Error>>;
+
+/// Registry of the active subscriptions shared between the
+/// [`Client`](super::Client) and the connection event loop, so that the
+/// subscriptions can be restored after reconnecting.
+#[derive(Debug, Clone, Default)]
+pub(super) struct SubscriptionRegistry(Arc<Mutex<HashMap<Topic, SubscriptionId>>>);
+
+impl SubscriptionRegistry {
+    pub(super) fn snapshot(&self) -> HashMap<Topic, SubscriptionId> {
+        self.lock().clone()
+    }
+
+    pub(super) fn get(&self, topic: &Topic) -> Option<SubscriptionId> {
+        self.lock().get(topic).cloned()
+    }
+
+    pub(super) fn topics(&self) -> Vec<Topic> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn insert(&self, topics: Vec<Topic>, ids: Vec<SubscriptionId>) {
+        self.lock().extend(topics.into_iter().zip(ids));
+    }
+
+    /// Updates the IDs of the topics, skipping the ones that have been
+    /// unsubscribed in the meantime.
+    fn update(&self, topics: Vec<Topic>, ids: Vec<SubscriptionId>) {
+        let mut subscriptions = self.lock();
+
+        for (topic, id) in topics.into_iter().zip(ids) {
+            if let Some(entry) = subscriptions.get_mut(&topic) {
+                *entry = id;
+            }
+        }
+    }
+
+    fn remove(&self, topics: Vec<Topic>) {
+        let mut subscriptions = self.lock();
+
+        for topic in topics {
+            subscriptions.remove(&topic);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Topic, SubscriptionId>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Change to the [`SubscriptionRegistry`] to be applied once the request
+/// succeeds.
+pub(super) enum SubscriptionUpdate {
+    Subscribe(Topic),
+    BatchSubscribe(Vec<Topic>),
+    Unsubscribe(Vec<Topic>),
+
+    /// Subscription restored by the client after reconnecting.
+    Resubscribe(Vec<Topic>),
+}
+
+impl SubscriptionUpdate {
+    pub(super) fn from_params(params: &Params) -> Option<Self> {
+        match params {
+            Params::Subscribe(data) => Some(Self::Subscribe(data.topic.clone())),
+
+            Params::BatchSubscribe(data) => Some(Self::BatchSubscribe(data.topics.clone())),
+
+            Params::Unsubscribe(data) => Some(Self::Unsubscribe(vec![data.topic.clone()])),
+
+            Params::BatchUnsubscribe(data) => Some(Self::Unsubscribe(
+                data.subscriptions
+                    .iter()
+                    .map(|sub| sub.topic.clone())
+                    .collect(),
+            )),
+
+            _ => None,
+        }
+    }
+
+    fn apply(self, registry: &SubscriptionRegistry, value: &serde_json::Value) {
+        match self {
+            Self::Subscribe(topic) => {
+                if let Ok(id) = SubscriptionId::deserialize(value) {
+                    registry.insert(vec![topic], vec![id]);
+                }
+            }
+
+            Self::BatchSubscribe(topics) => {
+                if let Ok(ids) = batch_response(value) {
+                    registry.insert(topics, ids);
+                }
+            }
+
+            Self::Unsubscribe(topics) => registry.remove(topics),
+
+            Self::Resubscribe(topics) => {
+                if let Ok(ids) = batch_response(value) {
+                    registry.update(topics, ids);
+                }
+            }
+        }
+    }
+}
+
+/// Response to a request changing the subscriptions, which is intercepted by
+/// the connection event loop to update the [`SubscriptionRegistry`] before
+/// it's forwarded to the caller.
+pub(super) struct TrackedResponse {
+    update: SubscriptionUpdate,
+    result: Result<serde_json::Value, Error>,
+    tx: Option<ResponseSender>,
+}
+
+impl TrackedResponse {
+    /// Returns the sender to pass along with the request, and the future
+    /// resolving with the response. The response is forwarded to `tx`, if
+    /// any.
+    pub(super) fn track(
+        update: SubscriptionUpdate,
+        tx: Option<ResponseSender>,
+    ) -> (ResponseSender, BoxFuture<'static, Self>) {
+        let (request_tx, rx) = oneshot::channel();
+
+        let response = async move {
+            Self {
+                update,
+                result: rx.await.unwrap_or(Err(Error::ChannelClosed)),
+                tx,
+            }
+        };
+
+        (request_tx, response.boxed())
+    }
+
+    /// Applies the update to the registry if the request succeeded, and
+    /// forwards the response. Returns the error of a failed resubscription.
+    pub(super) fn complete(self, registry: &SubscriptionRegistry) -> Option<(Vec<Topic>, Error)> {
+        match (self.update, self.result) {
+            (SubscriptionUpdate::Resubscribe(topics), result) => {
+                let error = match result.map(|value| batch_response(&value)) {
+                    Ok(Ok(ids)) => {
+                        registry.update(topics, ids);
+                        return None;
+                    }
+
+                    Ok(Err(err)) => Error::Deserialization(err),
+                    Err(err) => err,
+                };
+
+                Some((topics, error))
+            }
+
+            (update, result) => {
+                if let Ok(value) = &result {
+                    update.apply(registry, value);
+                }
+
+                if let Some(tx) = self.tx {
+                    tx.send(result).ok();
+                }
+
+                None
+            }
+        }
+    }
+}
+
+fn batch_response(
+    value: &serde_json::Value,
+) -> Result<<BatchSubscribe as RequestPayload>::Response, serde_json::Error> {
+    Deserialize::deserialize(value)
+}
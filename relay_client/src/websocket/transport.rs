@@ -0,0 +1,154 @@
+use {
+    super::{stream::handshake, TransportError, WebsocketClientError},
+    crate::{ConnectionOptions, HttpRequest},
+    futures_util::{future::BoxFuture, FutureExt, Sink, Stream},
+    tokio::net::TcpStream,
+    tokio_tungstenite::tungstenite::Message,
+};
+
+/// Bidirectional channel of the websocket messages that a
+/// [`ClientStream`](super::ClientStream) runs over.
+///
+/// Implemented for any [`Sink`] and [`Stream`] of the [`Message`]s with the
+/// [`TransportError`], such as a
+/// [`WebSocketStream`](tokio_tungstenite::WebSocketStream) created over a TCP,
+/// Unix or in-memory stream. Other error types can be mapped to the
+/// [`TransportError::Io`] variant.
+pub trait Transport:
+    Sink<Message, Error = TransportError>
+    + Stream<Item = Result<Message, TransportError>>
+    + Unpin
+    + Send
+    + 'static
+{
+}
+
+impl<T> Transport for T where
+    T: Sink<Message, Error = TransportError>
+        + Stream<Item = Result<Message, TransportError>>
+        + Unpin
+        + Send
+        + 'static
+{
+}
+
+/// Type-erased [`Transport`] used by the [`Client`](super::Client).
+pub type BoxTransport = Box<dyn Transport>;
+
+/// Establishes the [`Transport`]s for the [`Client`](super::Client), both on
+/// [`Client::connect()`](super::Client::connect) and when reconnecting
+/// automatically.
+///
+/// By default, the client uses the [`TcpConnector`]. See
+/// [`ClientBuilder::with_connector()`](super::ClientBuilder::with_connector).
+pub trait Connector: Send + Sync + 'static {
+    /// Opens a connection for the websocket handshake request, which is built
+    /// from the options.
+    fn connect(
+        &self,
+        request: HttpRequest<()>,
+        options: &ConnectionOptions,
+    ) -> BoxFuture<'static, Result<BoxTransport, WebsocketClientError>>;
+}
+
+impl<F> Connector for F
+where
+    F: Fn(
+            HttpRequest<()>,
+            &ConnectionOptions,
+        ) -> BoxFuture<'static, Result<BoxTransport, WebsocketClientError>>
+        + Send
+        + Sync
+        + 'static,
+{
+    fn connect(
+        &self,
+        request: HttpRequest<()>,
+        options: &ConnectionOptions,
+    ) -> BoxFuture<'static, Result<BoxTransport, WebsocketClientError>> {
+        self(request, options)
+    }
+}
+
+/// Default [`Connector`] opening a websocket connection over TCP, with TLS for
+/// the `wss` addresses, through the
+/// [`ConnectionOptions::proxy`](crate::ConnectionOptions::proxy) if any. The
+/// TLS connections use the
+/// [`ConnectionOptions::tls`](crate::ConnectionOptions::tls) configuration if
+/// any, or the default one otherwise, with the TLS backend enabled by the
+/// crate features. The
+/// [`ConnectionOptions::websocket`](crate::ConnectionOptions::websocket) limits
+/// and socket options are applied to the connection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpConnector;
+
+impl Connector for TcpConnector {
+    fn connect(
+        &self,
+        request: HttpRequest<()>,
+        options: &ConnectionOptions,
+    ) -> BoxFuture<'static, Result<BoxTransport, WebsocketClientError>> {
+        let uri = request.uri();
+        let host = uri
+            .host()
+            .unwrap_or_default()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_owned();
+        let secure = uri.scheme_str() == Some("wss");
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+        let proxy = options
+            .proxy
+            .clone()
+            .filter(|proxy| !proxy.is_bypassed(&host));
+        let tls = options.tls.clone().filter(|_| secure);
+        let config = options.websocket.clone();
+
+        async move {
+            let stream = match proxy {
+                Some(proxy) => proxy.connect(&host, port).await?,
+                None => TcpStream::connect((host.as_str(), port))
+                    .await
+                    .map_err(|err| WebsocketClientError::ConnectionFailed(err.into()))?,
+            };
+
+            config
+                .configure_socket(&stream)
+                .map_err(|err| WebsocketClientError::ConnectionFailed(err.into()))?;
+
+            // The pins are verified before sending the upgrade request.
+            #[cfg(feature = "native-tls")]
+            let socket = match &tls {
+                Some(tls) => {
+                    let stream = tls.connect(&host, stream).await?;
+                    let config = Some(config.as_tungstenite());
+
+                    tokio_tungstenite::client_async_with_config(request, stream, config)
+                        .await
+                        .map_err(WebsocketClientError::ConnectionFailed)?
+                        .0
+                }
+
+                None => handshake(request, stream, &config, None)
+                    .await
+                    .map_err(WebsocketClientError::ConnectionFailed)?,
+            };
+
+            // With `rustls`, the connector verifies the pins during the handshake.
+            #[cfg(not(feature = "native-tls"))]
+            let socket = {
+                let connector = tls.map(|tls| tls.connector()).transpose()?;
+
+                handshake(request, stream, &config, connector)
+                    .await
+                    .map_err(WebsocketClientError::ConnectionFailed)?
+            };
+
+            let socket: BoxTransport = Box::new(socket);
+
+            Ok(socket)
+        }
+        .boxed()
+    }
+}
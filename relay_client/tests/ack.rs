@@ -0,0 +1,104 @@
+use {
+    common::{connect, next_event, options, Relay},
+    relay_client::websocket::{AckMode, ClientEvent},
+    relay_rpc::{
+        domain::Topic,
+        rpc::{GenericError, Response},
+    },
+    serde_json::json,
+    std::time::Duration,
+};
+
+mod common;
+
+#[tokio::test]
+async fn auto_ack() {
+    let mut relay = Relay::new();
+    let (_client, mut events, mut conn) = connect(&mut relay, &options()).await;
+
+    conn.deliver(1, "sub", &Topic::generate(), "message").await;
+
+    match next_event(&mut events).await {
+        ClientEvent::Message(message) => assert!(message.ack.is_none()),
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    match conn.recv_response().await {
+        Response::Success(response) => {
+            assert_eq!(response.id.into_value(), 1);
+            assert_eq!(response.result, json!(true));
+        }
+
+        response => panic!("unexpected response: {response:?}"),
+    }
+}
+
+#[tokio::test]
+async fn manual_ack_timeout() {
+    let mut relay = Relay::new();
+    let options = options().with_ack_mode(AckMode::Manual {
+        timeout: Some(Duration::from_millis(50)),
+    });
+    let (_client, mut events, mut conn) = connect(&mut relay, &options).await;
+
+    conn.deliver(1, "sub", &Topic::generate(), "message").await;
+
+    let ack = match next_event(&mut events).await {
+        ClientEvent::Message(message) => message.ack.expect("manual ack"),
+        event => panic!("unexpected event: {event:?}"),
+    };
+
+    // The message is acknowledged once the timeout elapses.
+    match conn.recv_response().await {
+        Response::Success(response) => assert_eq!(response.id.into_value(), 1),
+        response => panic!("unexpected response: {response:?}"),
+    }
+
+    assert!(!ack.ack().unwrap());
+}
+
+#[tokio::test]
+async fn manual_ack() {
+    let mut relay = Relay::new();
+    let options = options().with_ack_mode(AckMode::Manual { timeout: None });
+    let (_client, mut events, mut conn) = connect(&mut relay, &options).await;
+    let topic = Topic::generate();
+
+    conn.deliver(1, "sub", &topic, "acked").await;
+    conn.deliver(2, "sub", &topic, "rejected").await;
+
+    let mut acks = Vec::new();
+
+    for _ in 0..2 {
+        match next_event(&mut events).await {
+            ClientEvent::Message(message) => acks.push(message.ack.expect("manual ack")),
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    // Nothing is acknowledged until requested.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), conn.recv_response())
+            .await
+            .is_err()
+    );
+
+    assert!(acks[1].reject(GenericError::TooManyRequests).unwrap());
+
+    match conn.recv_response().await {
+        Response::Error(response) => assert_eq!(response.id.into_value(), 2),
+        response => panic!("unexpected response: {response:?}"),
+    }
+
+    assert!(acks[0].ack().unwrap());
+    assert!(!acks[0].ack().unwrap());
+
+    match conn.recv_response().await {
+        Response::Success(response) => {
+            assert_eq!(response.id.into_value(), 1);
+            assert_eq!(response.result, json!(true));
+        }
+
+        response => panic!("unexpected response: {response:?}"),
+    }
+}
@@ -0,0 +1,218 @@
+use {
+    common::{options, timeout, Relay, RelayConnection},
+    futures_util::StreamExt,
+    relay_client::{
+        error::Error,
+        websocket::{
+            Client,
+            ClientEvent,
+            ClientEvents,
+            EmptyResponseFuture,
+            EventChannel,
+            OverflowPolicy,
+            QueueConfig,
+            QueueDepth,
+            SubscriptionStream,
+            WebsocketClientError,
+        },
+    },
+    relay_rpc::{
+        domain::Topic,
+        rpc::{Publish, Response},
+    },
+    serde_json::json,
+    std::time::Duration,
+};
+
+mod common;
+
+const TTL: Duration = Duration::from_secs(300);
+
+fn queues(overflow: OverflowPolicy) -> QueueConfig {
+    QueueConfig::default()
+        .with_control_capacity(1)
+        .with_outbound_capacity(1)
+        .with_overflow(overflow)
+}
+
+/// Waits until the queues reach the depth, i.e. the connection event loop has
+/// processed the requests.
+async fn wait_depth(client: &Client, control: usize, outbound: usize) {
+    timeout(async {
+        loop {
+            let QueueDepth {
+                control: c,
+                outbound: o,
+                ..
+            } = client.queue_depth();
+
+            if (c, o) == (control, outbound) {
+                return;
+            }
+
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+}
+
+/// Lets the connection event loop process the sent requests.
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+/// Publishes a message larger than the in-memory stream buffer, which stalls
+/// the transport until the relay reads it.
+fn stall(client: &Client, topic: &Topic) -> EmptyResponseFuture<Publish> {
+    client.publish(topic.clone(), "x".repeat(1 << 17), 0, TTL, false)
+}
+
+#[tokio::test]
+async fn queue_full() {
+    let mut relay = Relay::new();
+    let (client, _events) = relay
+        .builder()
+        .with_queues(queues(OverflowPolicy::Fail))
+        .build_with_events(EventChannel::Broadcast(16));
+
+    timeout(client.connect(&options())).await.unwrap();
+    let _conn = relay.accept().await;
+
+    let topic = Topic::generate();
+    let stalled = stall(&client, &topic);
+    settle().await;
+
+    let outbound = client.publish(topic.clone(), "outbound", 0, TTL, false);
+    wait_depth(&client, 0, 1).await;
+
+    let control = client.publish(topic.clone(), "control", 0, TTL, false);
+    wait_depth(&client, 1, 1).await;
+
+    assert!(matches!(
+        client
+            .publish(topic.clone(), "overflow", 0, TTL, false)
+            .await,
+        Err(Error::WebsocketClient(WebsocketClientError::QueueFull))
+    ));
+
+    // Disconnecting doesn't wait for the queued requests, even though the
+    // transport is stalled.
+    tokio::time::pause();
+    timeout(client.disconnect()).await.unwrap();
+
+    for response in [stalled, outbound, control] {
+        assert!(timeout(response).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn wait_for_capacity() {
+    let mut relay = Relay::new();
+    let (client, _events) = relay
+        .builder()
+        .with_queues(queues(OverflowPolicy::Wait))
+        .build_with_events(EventChannel::Broadcast(16));
+
+    timeout(client.connect(&options())).await.unwrap();
+    let mut conn = relay.accept().await;
+
+    let topic = Topic::generate();
+    let stalled = tokio::spawn(stall(&client, &topic));
+    let outbound = tokio::spawn(client.publish(topic.clone(), "outbound", 0, TTL, false));
+    wait_depth(&client, 0, 1).await;
+
+    let control = tokio::spawn(client.publish(topic.clone(), "control", 0, TTL, false));
+    wait_depth(&client, 1, 1).await;
+
+    let waiting = tokio::spawn(client.publish(topic.clone(), "waiting", 0, TTL, false));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(!waiting.is_finished());
+
+    for _ in 0..4 {
+        conn.handle(json!(true)).await;
+    }
+
+    for response in [stalled, outbound, control, waiting] {
+        timeout(response).await.unwrap().unwrap();
+    }
+
+    wait_depth(&client, 0, 0).await;
+}
+
+async fn subscribe_stream(
+    overflow: OverflowPolicy,
+) -> (SubscriptionStream, ClientEvents, RelayConnection) {
+    let mut relay = Relay::new();
+    let (client, events) = relay
+        .builder()
+        .with_queues(
+            QueueConfig::default()
+                .with_stream_capacity(1)
+                .with_overflow(overflow),
+        )
+        .build_with_events(EventChannel::Bounded(16));
+
+    timeout(client.connect(&options())).await.unwrap();
+    let mut conn = relay.accept().await;
+
+    let (stream, _) = tokio::join!(
+        client.subscribe_stream(Topic::generate()),
+        conn.handle(json!("sub"))
+    );
+
+    (stream.unwrap(), events, conn)
+}
+
+#[tokio::test]
+async fn stream_wait_for_capacity() {
+    let (mut stream, _events, mut conn) = subscribe_stream(OverflowPolicy::Wait).await;
+    let topic = stream.topic().clone();
+
+    for id in 1..=3 {
+        conn.deliver(id, "sub", &topic, &format!("message {id}"))
+            .await;
+    }
+
+    for id in 1..=3 {
+        let message = timeout(stream.next()).await.unwrap();
+        assert_eq!(&*message.message, format!("message {id}"));
+    }
+}
+
+#[tokio::test]
+async fn stream_full() {
+    let (mut stream, mut events, mut conn) = subscribe_stream(OverflowPolicy::Fail).await;
+    let topic = stream.topic().clone();
+
+    conn.deliver(1, "sub", &topic, "delivered").await;
+    conn.deliver(2, "sub", &topic, "discarded").await;
+
+    assert!(matches!(
+        timeout(events.next()).await,
+        Some(ClientEvent::Connected)
+    ));
+
+    match timeout(events.next()).await {
+        Some(ClientEvent::InboundError(error)) => assert!(matches!(
+            *error,
+            Error::WebsocketClient(WebsocketClientError::QueueFull)
+        )),
+
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    assert_eq!(&*timeout(stream.next()).await.unwrap().message, "delivered");
+
+    // Only the delivered message is acknowledged.
+    match conn.recv_response().await {
+        Response::Success(response) => assert_eq!(response.id.into_value(), 1),
+        response => panic!("unexpected response: {response:?}"),
+    }
+
+    match conn.recv_response().await {
+        Response::Error(response) => assert_eq!(response.id.into_value(), 2),
+        response => panic!("unexpected response: {response:?}"),
+    }
+}
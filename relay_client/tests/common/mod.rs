@@ -0,0 +1,307 @@
+//! Scripted stand-in for the Relay, connected to the [`Client`] over in-memory
+//! streams instead of TCP.
+
+#![allow(dead_code)]
+
+use {
+    futures_util::{FutureExt, SinkExt, StreamExt},
+    relay_client::{
+        websocket::{
+            BoxTransport,
+            Client,
+            ClientBuilder,
+            ClientEvent,
+            ClientEvents,
+            EventChannel,
+            ReconnectPolicy,
+            WebsocketClientError,
+        },
+        ConnectionOptions,
+        HttpRequest,
+    },
+    relay_rpc::{
+        auth::{ed25519_dalek::Keypair, rand, AuthToken, SerializedAuthToken},
+        domain::{MessageId, SubscriptionId, Topic},
+        rpc::{
+            ErrorData,
+            ErrorResponse,
+            Params,
+            Payload,
+            Request,
+            Response,
+            Subscription,
+            SubscriptionData,
+            SuccessfulResponse,
+        },
+    },
+    std::{borrow::Cow, time::Duration},
+    tokio::{io::DuplexStream, sync::mpsc},
+    tokio_tungstenite::{
+        tungstenite::{
+            protocol::{frame::coding::CloseCode, CloseFrame, Role},
+            Message,
+        },
+        WebSocketStream,
+    },
+};
+
+/// How long the tests wait for the client before failing.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Waits for the future, failing the test if it doesn't complete in time.
+pub async fn timeout<F: std::future::Future>(future: F) -> F::Output {
+    tokio::time::timeout(TIMEOUT, future)
+        .await
+        .expect("timed out")
+}
+
+/// Waits until the condition holds, e.g. the client has processed a response.
+pub async fn eventually(condition: impl Fn() -> bool) {
+    timeout(async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    })
+    .await
+}
+
+/// Returns an auth token signed by a new key.
+pub fn auth_token() -> SerializedAuthToken {
+    let key = Keypair::generate(&mut rand::thread_rng());
+
+    AuthToken::new("http://example.com")
+        .aud("wss://relay.walletconnect.com")
+        .as_jwt(&key)
+        .unwrap()
+}
+
+pub fn options() -> ConnectionOptions {
+    ConnectionOptions::new("1234", auth_token()).with_proxy(None)
+}
+
+/// Returns the options reconnecting shortly after the connection is lost.
+pub fn reconnecting() -> ConnectionOptions {
+    options().with_reconnect_policy(
+        ReconnectPolicy::default().with_initial_delay(Duration::from_millis(10)),
+    )
+}
+
+/// Returns the close frame of a relay restart.
+pub fn restart() -> Option<CloseFrame<'static>> {
+    Some(CloseFrame {
+        code: CloseCode::Restart,
+        reason: Cow::Borrowed("restart"),
+    })
+}
+
+/// Waits for the next client event.
+pub async fn next_event(events: &mut ClientEvents) -> ClientEvent {
+    timeout(events.next()).await.expect("events closed")
+}
+
+/// Connects a new client to the relay, and waits for it to be connected.
+pub async fn connect(
+    relay: &mut Relay,
+    options: &ConnectionOptions,
+) -> (Client, ClientEvents, RelayConnection) {
+    let (client, mut events) = relay.builder().build_with_events(EventChannel::Bounded(16));
+
+    timeout(client.connect(options)).await.unwrap();
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+
+    let conn = relay.accept().await;
+
+    (client, events, conn)
+}
+
+/// Closes the connection from the relay side, and waits for the client to
+/// reconnect.
+pub async fn restart_connection(
+    relay: &mut Relay,
+    conn: &mut RelayConnection,
+    events: &mut ClientEvents,
+) -> RelayConnection {
+    conn.close(restart()).await;
+
+    assert!(matches!(
+        next_event(events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+    assert!(matches!(
+        next_event(events).await,
+        ClientEvent::Reconnecting { attempt: 1, .. }
+    ));
+
+    let conn = relay.accept().await;
+
+    assert!(matches!(next_event(events).await, ClientEvent::Connected));
+    assert!(matches!(
+        next_event(events).await,
+        ClientEvent::Reconnected { attempts: 1 }
+    ));
+
+    conn
+}
+
+/// Returns the ID of the client subscription to the topic.
+pub fn subscription_id(client: &Client, topic: &Topic) -> Option<SubscriptionId> {
+    client.subscriptions().get(topic).cloned()
+}
+
+/// Accepts the connections of the clients built with [`Relay::builder()`].
+pub struct Relay {
+    connections: mpsc::UnboundedReceiver<RelayConnection>,
+    connector: ClientBuilder,
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        let (tx, connections) = mpsc::unbounded_channel();
+
+        let connector = move |request: HttpRequest<()>, options: &ConnectionOptions| {
+            let tx = tx.clone();
+            let config = options.websocket.as_tungstenite();
+
+            async move {
+                let (client, server) = tokio::io::duplex(1 << 16);
+
+                let socket = WebSocketStream::from_raw_socket(server, Role::Server, None).await;
+                tx.send(RelayConnection { request, socket }).ok();
+
+                let socket =
+                    WebSocketStream::from_raw_socket(client, Role::Client, Some(config)).await;
+
+                Ok::<_, WebsocketClientError>(Box::new(socket) as BoxTransport)
+            }
+            .boxed()
+        };
+
+        Self {
+            connections,
+            connector: Client::builder().with_connector(connector),
+        }
+    }
+
+    /// Returns a builder of the clients connecting to this relay.
+    pub fn builder(&self) -> ClientBuilder {
+        self.connector.clone()
+    }
+
+    /// Waits for the next client connection.
+    pub async fn accept(&mut self) -> RelayConnection {
+        timeout(self.connections.recv())
+            .await
+            .expect("relay closed")
+    }
+}
+
+/// Server side of a client connection.
+pub struct RelayConnection {
+    /// The websocket handshake request of the client.
+    pub request: HttpRequest<()>,
+    socket: WebSocketStream<DuplexStream>,
+}
+
+impl RelayConnection {
+    /// Returns the auth token passed in the query string of the handshake
+    /// request.
+    pub fn auth(&self) -> Option<&str> {
+        self.request
+            .uri()
+            .query()?
+            .split('&')
+            .find_map(|param| param.strip_prefix("auth="))
+    }
+
+    /// Receives the next websocket message, including the control frames.
+    pub async fn recv_message(&mut self) -> Option<Message> {
+        timeout(self.socket.next())
+            .await
+            .map(|result| result.expect("transport error"))
+    }
+
+    /// Receives the next payload, skipping the control frames.
+    pub async fn recv_payload(&mut self) -> Payload {
+        loop {
+            match self.recv_message().await.expect("connection closed") {
+                Message::Text(text) => return serde_json::from_str(&text).unwrap(),
+                Message::Close(_) => panic!("connection closed"),
+                _ => {}
+            }
+        }
+    }
+
+    /// Receives the next request from the client.
+    pub async fn recv(&mut self) -> Request {
+        match self.recv_payload().await {
+            Payload::Request(request) => request,
+            Payload::Response(response) => panic!("unexpected response: {response:?}"),
+        }
+    }
+
+    /// Receives the next response from the client, e.g. an acknowledgement.
+    pub async fn recv_response(&mut self) -> Response {
+        match self.recv_payload().await {
+            Payload::Response(response) => response,
+            Payload::Request(request) => panic!("unexpected request: {request:?}"),
+        }
+    }
+
+    /// Receives the next request and responds to it with the result.
+    pub async fn handle(&mut self, result: serde_json::Value) -> Request {
+        let request = self.recv().await;
+        self.respond(request.id, result).await;
+        request
+    }
+
+    pub async fn respond(&mut self, id: MessageId, result: serde_json::Value) {
+        let response = Response::Success(SuccessfulResponse::new(id, result));
+        self.send(Payload::Response(response)).await;
+    }
+
+    pub async fn respond_error(&mut self, id: MessageId, code: i32, message: &str) {
+        let error = ErrorData {
+            code,
+            message: message.to_owned(),
+            data: None,
+        };
+        let response = Response::Error(ErrorResponse::new(id, error));
+        self.send(Payload::Response(response)).await;
+    }
+
+    /// Sends a request to the client, e.g. a subscription message.
+    pub async fn request(&mut self, id: MessageId, params: Params) {
+        self.send(Payload::Request(Request::new(id, params))).await;
+    }
+
+    /// Delivers a message published on the topic to the client.
+    pub async fn deliver(&mut self, id: u64, subscription_id: &str, topic: &Topic, message: &str) {
+        let params = Params::Subscription(Subscription {
+            id: SubscriptionId::from(subscription_id),
+            data: SubscriptionData {
+                topic: topic.clone(),
+                message: message.into(),
+                published_at: 0,
+                tag: 0,
+            },
+        });
+
+        self.request(MessageId::new(id), params).await;
+    }
+
+    pub async fn send(&mut self, payload: Payload) {
+        let text = serde_json::to_string(&payload).unwrap();
+        self.send_message(Message::Text(text)).await;
+    }
+
+    pub async fn send_message(&mut self, message: Message) {
+        self.socket.send(message).await.unwrap();
+    }
+
+    pub async fn close(&mut self, frame: Option<CloseFrame<'static>>) {
+        self.socket.close(frame).await.ok();
+    }
+}
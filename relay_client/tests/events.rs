@@ -0,0 +1,95 @@
+use {
+    common::{connect, next_event, options, restart, timeout, Relay},
+    futures_util::StreamExt,
+    relay_client::websocket::{ClientEvent, EventChannel},
+    relay_rpc::{
+        domain::Topic,
+        rpc::{Params, Payload},
+    },
+    serde_json::json,
+    std::time::Duration,
+};
+
+mod common;
+
+#[tokio::test]
+async fn events() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &options()).await;
+    let topic = Topic::generate();
+
+    for id in 1..=3 {
+        conn.deliver(id, "sub", &topic, &format!("message {id}"))
+            .await;
+    }
+
+    for id in 1..=3 {
+        match next_event(&mut events).await {
+            ClientEvent::Message(message) => {
+                assert_eq!(message.topic, topic);
+                assert_eq!(&*message.message, format!("message {id}"));
+            }
+
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    conn.close(restart()).await;
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+
+    // Dropping the client ends the events.
+    drop(client);
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(None)
+    ));
+    assert!(timeout(events.next()).await.is_none());
+}
+
+#[tokio::test]
+async fn bounded_events() {
+    let mut relay = Relay::new();
+    let (client, mut events) = relay.builder().build_with_events(EventChannel::Bounded(1));
+
+    timeout(client.connect(&options())).await.unwrap();
+    let mut conn = relay.accept().await;
+    let topic = Topic::generate();
+
+    for id in 1..=3 {
+        conn.deliver(id, "sub", &topic, &format!("message {id}"))
+            .await;
+    }
+
+    // The requests are sent while the events wait for the consumer.
+    let response = client.publish(topic.clone(), "message", 0, Duration::from_secs(300), false);
+
+    let request = loop {
+        if let Payload::Request(request) = conn.recv_payload().await {
+            break request;
+        }
+    };
+
+    assert!(matches!(request.params, Params::Publish(_)));
+    conn.respond(request.id, json!(true)).await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+
+    for id in 1..=3 {
+        match next_event(&mut events).await {
+            ClientEvent::Message(message) => {
+                assert_eq!(&*message.message, format!("message {id}"))
+            }
+
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+
+    timeout(response).await.unwrap();
+    timeout(client.disconnect()).await.unwrap();
+}
@@ -0,0 +1,52 @@
+use {
+    common::{connect, next_event, options, Relay},
+    relay_client::websocket::{is_pong_timeout, ClientEvent, KeepaliveConfig},
+    std::time::Duration,
+    tokio::sync::oneshot,
+};
+
+mod common;
+
+#[tokio::test]
+async fn pong_timeout() {
+    let mut relay = Relay::new();
+    let keepalive = KeepaliveConfig::default()
+        .with_interval(Duration::from_millis(50))
+        .with_timeout(Duration::from_millis(100));
+    let (_client, mut events, mut conn) =
+        connect(&mut relay, &options().with_keepalive(keepalive)).await;
+
+    // Reading makes the relay respond to the pings.
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    let reader = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = &mut stop_rx => return conn,
+                _ = conn.recv_message() => {}
+            }
+        }
+    });
+
+    for _ in 0..2 {
+        assert!(matches!(
+            next_event(&mut events).await,
+            ClientEvent::Pong(_)
+        ));
+    }
+
+    stop_tx.send(()).unwrap();
+    let _conn = reader.await.unwrap();
+
+    loop {
+        match next_event(&mut events).await {
+            ClientEvent::Pong(_) => {}
+
+            ClientEvent::Disconnected(Some(frame)) => {
+                assert!(is_pong_timeout(&frame));
+                break;
+            }
+
+            event => panic!("unexpected event: {event:?}"),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use {
+    common::{connect, next_event, options, Relay},
+    relay_client::{
+        error::Error,
+        websocket::{ClientEvent, WebsocketClientError, WebsocketConfig},
+    },
+    relay_rpc::domain::Topic,
+};
+
+mod common;
+
+#[tokio::test]
+async fn message_within_limit() {
+    let mut relay = Relay::new();
+    let options = options().with_websocket(WebsocketConfig::default().with_max_message_size(1024));
+    let (_client, mut events, mut conn) = connect(&mut relay, &options).await;
+
+    conn.deliver(1, "sub", &Topic::generate(), &"x".repeat(512))
+        .await;
+
+    match next_event(&mut events).await {
+        ClientEvent::Message(message) => assert_eq!(message.message.len(), 512),
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
+
+#[tokio::test]
+async fn message_too_large() {
+    let mut relay = Relay::new();
+    let options = options().with_websocket(WebsocketConfig::default().with_max_message_size(1024));
+    let (_client, mut events, mut conn) = connect(&mut relay, &options).await;
+
+    conn.deliver(1, "sub", &Topic::generate(), &"x".repeat(2048))
+        .await;
+
+    match next_event(&mut events).await {
+        ClientEvent::InboundError(error) => assert!(matches!(
+            *error,
+            Error::WebsocketClient(WebsocketClientError::MessageTooLarge { max_size: 1024, .. })
+        )),
+
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(None)
+    ));
+}
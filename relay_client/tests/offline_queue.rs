@@ -0,0 +1,105 @@
+use {
+    common::{connect, eventually, next_event, options, timeout, Relay},
+    relay_client::{
+        error::Error,
+        websocket::{ClientEvent, OfflineQueueConfig, WebsocketClientError},
+        ConnectionOptions,
+    },
+    relay_rpc::{domain::Topic, rpc::Params},
+    serde_json::json,
+    std::{borrow::Cow, time::Duration},
+    tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+};
+
+mod common;
+
+const TTL: Duration = Duration::from_secs(300);
+
+fn queueing() -> ConnectionOptions {
+    options().with_offline_queue(OfflineQueueConfig::default())
+}
+
+#[tokio::test]
+async fn queue_while_disconnected() {
+    let mut relay = Relay::new();
+    let (client, _events, _conn) = connect(&mut relay, &queueing()).await;
+
+    timeout(client.disconnect()).await.unwrap();
+
+    let topic = Topic::generate();
+    let response = client.publish(topic.clone(), "queued", 0, TTL, false);
+
+    eventually(|| client.queue_depth().offline == 1).await;
+
+    timeout(client.connect(&queueing())).await.unwrap();
+    let mut conn = relay.accept().await;
+
+    let request = conn.handle(json!(true)).await;
+    let Params::Publish(publish) = request.params else {
+        panic!("unexpected request: {:?}", request.params);
+    };
+
+    assert_eq!(publish.topic, topic);
+    assert_eq!(&*publish.message, "queued");
+
+    timeout(response).await.unwrap();
+}
+
+#[tokio::test]
+async fn queue_after_connection_closed() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &queueing()).await;
+
+    // The connection isn't reconnected without a reconnect policy.
+    conn.close(Some(CloseFrame {
+        code: CloseCode::Normal,
+        reason: Cow::Borrowed(""),
+    }))
+    .await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+
+    let response = client.publish(Topic::generate(), "queued", 0, TTL, false);
+    eventually(|| client.queue_depth().offline == 1).await;
+
+    timeout(client.connect(&queueing())).await.unwrap();
+    let mut conn = relay.accept().await;
+
+    assert!(matches!(
+        conn.handle(json!(true)).await.params,
+        Params::Publish(_)
+    ));
+    timeout(response).await.unwrap();
+}
+
+#[tokio::test]
+async fn queue_expiry_and_shutdown() {
+    let mut relay = Relay::new();
+    let options = options()
+        .with_offline_queue(OfflineQueueConfig::default().with_ttl(Duration::from_millis(100)));
+    let (client, _events, _conn) = connect(&mut relay, &options).await;
+
+    timeout(client.disconnect()).await.unwrap();
+
+    let expired = client.publish(Topic::generate(), "expired", 0, TTL, false);
+
+    assert!(matches!(
+        timeout(expired).await,
+        Err(Error::WebsocketClient(
+            WebsocketClientError::OfflineQueueExpired
+        ))
+    ));
+
+    // Dropping the client fails the requests still queued.
+    let pending = client.publish(Topic::generate(), "pending", 0, TTL, false);
+    eventually(|| client.queue_depth().offline == 1).await;
+    drop(client);
+
+    assert!(matches!(
+        timeout(pending).await,
+        Err(Error::WebsocketClient(WebsocketClientError::NotConnected))
+    ));
+}
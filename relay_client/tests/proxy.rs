@@ -0,0 +1,236 @@
+use {
+    common::{options, timeout},
+    futures_util::{SinkExt, StreamExt},
+    relay_client::{
+        proxy::Proxy,
+        websocket::{Client, EventChannel},
+    },
+    relay_rpc::{
+        domain::Topic,
+        rpc::{Payload, Response, SuccessfulResponse},
+    },
+    serde_json::json,
+    std::{
+        net::IpAddr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    },
+    tokio_tungstenite::tungstenite::Message,
+};
+
+mod common;
+
+/// Targets requested from the proxy stand-ins.
+type Targets = Arc<Mutex<Vec<String>>>;
+
+/// Starts a websocket server on the loopback address, which responds to the
+/// first request of the client. Returns its port.
+async fn relay() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+        while let Some(Ok(message)) = socket.next().await {
+            if let Message::Text(text) = message {
+                let Payload::Request(request) = serde_json::from_str(&text).unwrap() else {
+                    continue;
+                };
+
+                let response = Response::Success(SuccessfulResponse::new(request.id, json!(true)));
+                let text = serde_json::to_string(&Payload::Response(response)).unwrap();
+                socket.send(Message::Text(text)).await.unwrap();
+            }
+        }
+    });
+
+    port
+}
+
+/// Starts an HTTP proxy stand-in tunneling the `CONNECT` requests with the
+/// credentials `user:pass`. Returns its URL.
+async fn http_proxy(targets: Targets) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let targets = targets.clone();
+
+            tokio::spawn(async move {
+                let mut head = Vec::new();
+
+                while !head.ends_with(b"\r\n\r\n") {
+                    head.push(stream.read_u8().await.unwrap());
+                }
+
+                let head = String::from_utf8(head).unwrap();
+                let target = head.split_whitespace().nth(1).unwrap().to_owned();
+                targets.lock().unwrap().push(target.clone());
+
+                // The credentials are `user:pass`.
+                if !head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n") {
+                    stream
+                        .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                        .await
+                        .unwrap();
+                    return;
+                }
+
+                let mut upstream = TcpStream::connect(target).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                    .await
+                    .unwrap();
+                tokio::io::copy_bidirectional(&mut stream, &mut upstream)
+                    .await
+                    .ok();
+            });
+        }
+    });
+
+    format!("http://user:pass@{addr}")
+}
+
+/// Starts a SOCKS5 proxy stand-in without authentication. Returns its
+/// address.
+async fn socks5_proxy(targets: Targets) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let targets = targets.clone();
+
+            tokio::spawn(async move {
+                // Greeting, accepting the "no authentication" method.
+                assert_eq!(stream.read_u8().await.unwrap(), 5);
+                let mut methods = vec![0; stream.read_u8().await.unwrap() as usize];
+                stream.read_exact(&mut methods).await.unwrap();
+                assert!(methods.contains(&0));
+                stream.write_all(&[5, 0]).await.unwrap();
+
+                // Connect request.
+                let mut header = [0; 4];
+                stream.read_exact(&mut header).await.unwrap();
+                assert_eq!(header[..2], [5, 1]);
+
+                let host = match header[3] {
+                    1 => {
+                        let mut ip = [0; 4];
+                        stream.read_exact(&mut ip).await.unwrap();
+                        IpAddr::from(ip).to_string()
+                    }
+
+                    3 => {
+                        let mut name = vec![0; stream.read_u8().await.unwrap() as usize];
+                        stream.read_exact(&mut name).await.unwrap();
+                        String::from_utf8(name).unwrap()
+                    }
+
+                    4 => {
+                        let mut ip = [0; 16];
+                        stream.read_exact(&mut ip).await.unwrap();
+                        format!("[{}]", IpAddr::from(ip))
+                    }
+
+                    kind => panic!("unexpected address type: {kind}"),
+                };
+
+                let port = stream.read_u16().await.unwrap();
+                let target = format!("{host}:{port}");
+                targets.lock().unwrap().push(target.clone());
+
+                let Ok(mut upstream) = TcpStream::connect(target).await else {
+                    // Connection refused.
+                    stream.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]).await.ok();
+                    return;
+                };
+
+                stream
+                    .write_all(&[5, 0, 0, 1, 0, 0, 0, 0, 0, 0])
+                    .await
+                    .unwrap();
+                tokio::io::copy_bidirectional(&mut stream, &mut upstream)
+                    .await
+                    .ok();
+            });
+        }
+    });
+
+    addr.to_string()
+}
+
+/// Connects to the relay at `localhost` through the proxy, and publishes a
+/// message.
+async fn publish(proxy: Proxy, port: u16) {
+    let (client, _events) = Client::builder().build_with_events(EventChannel::Broadcast(16));
+    let options = options()
+        .with_address(format!("ws://localhost:{port}"))
+        .with_proxy(proxy);
+
+    timeout(client.connect(&options)).await.unwrap();
+
+    timeout(client.publish(
+        Topic::generate(),
+        "message",
+        0,
+        Duration::from_secs(300),
+        false,
+    ))
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn http_connect() {
+    let targets = Targets::default();
+    let port = relay().await;
+    let proxy = Proxy::new(&http_proxy(targets.clone()).await).unwrap();
+
+    publish(proxy, port).await;
+
+    assert_eq!(*targets.lock().unwrap(), [format!("localhost:{port}")]);
+}
+
+#[tokio::test]
+async fn socks5_local_dns() {
+    let targets = Targets::default();
+    let port = relay().await;
+    let proxy = Proxy::new(&format!("socks5://{}", socks5_proxy(targets.clone()).await)).unwrap();
+
+    publish(proxy, port).await;
+
+    // The proxy is given the resolved addresses, trying the next one if it can't
+    // connect to one of them.
+    let targets = targets.lock().unwrap();
+    assert_eq!(targets.last().unwrap(), &format!("127.0.0.1:{port}"));
+
+    for target in targets.iter() {
+        let (ip, _) = target.rsplit_once(':').unwrap();
+        assert!(ip.trim_matches(['[', ']']).parse::<IpAddr>().is_ok());
+    }
+}
+
+#[tokio::test]
+async fn socks5_remote_dns() {
+    let targets = Targets::default();
+    let port = relay().await;
+    let proxy = Proxy::new(&format!(
+        "socks5h://{}",
+        socks5_proxy(targets.clone()).await
+    ))
+    .unwrap();
+
+    publish(proxy, port).await;
+
+    assert_eq!(*targets.lock().unwrap(), [format!("localhost:{port}")]);
+}
@@ -0,0 +1,82 @@
+use {
+    common::{connect, reconnecting, timeout, Relay},
+    relay_client::{error::Error, websocket::PublishRetryPolicy},
+    relay_rpc::domain::Topic,
+    serde_json::json,
+    std::time::Duration,
+};
+
+mod common;
+
+#[tokio::test]
+async fn publish_with_retry() {
+    let mut relay = Relay::new();
+    let (client, _events, mut conn) = connect(&mut relay, &reconnecting()).await;
+
+    let response = tokio::spawn({
+        let client = client.clone();
+
+        async move {
+            client
+                .publish_with_retry(
+                    Topic::generate(),
+                    "message",
+                    0,
+                    Duration::from_secs(300),
+                    false,
+                    &PublishRetryPolicy::default(),
+                )
+                .await
+        }
+    });
+
+    // The connection is lost before the relay responds.
+    let first = conn.recv().await;
+    drop(conn);
+
+    let mut conn = relay.accept().await;
+    let retry = conn.handle(json!(true)).await;
+
+    assert_eq!(retry.id, first.id);
+    assert_eq!(retry.params, first.params);
+
+    timeout(response).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn publish_error_not_retried() {
+    let mut relay = Relay::new();
+    let (client, _events, mut conn) = connect(&mut relay, &reconnecting()).await;
+
+    let response = tokio::spawn({
+        let client = client.clone();
+
+        async move {
+            client
+                .publish_with_retry(
+                    Topic::generate(),
+                    "message",
+                    0,
+                    Duration::from_secs(300),
+                    false,
+                    &PublishRetryPolicy::default(),
+                )
+                .await
+        }
+    });
+
+    // The Relay has received the request, so the error is final.
+    let request = conn.recv().await;
+    conn.respond_error(request.id, -32000, "publish failed")
+        .await;
+
+    assert!(matches!(
+        timeout(response).await.unwrap(),
+        Err(Error::Rpc { .. })
+    ));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), conn.recv_payload())
+            .await
+            .is_err()
+    );
+}
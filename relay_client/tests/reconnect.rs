@@ -0,0 +1,120 @@
+use {
+    common::{auth_token, connect, next_event, options, reconnecting, restart_connection, Relay},
+    futures_util::StreamExt,
+    relay_client::websocket::{ClientEvent, ConnectionState, ReconnectPolicy},
+    std::{borrow::Cow, time::Duration},
+    tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+};
+
+mod common;
+
+fn unauthorized() -> Option<CloseFrame<'static>> {
+    Some(CloseFrame {
+        code: CloseCode::from(3000),
+        reason: Cow::Borrowed("unauthorized"),
+    })
+}
+
+#[tokio::test]
+async fn reconnect() {
+    let mut relay = Relay::new();
+    let (_client, mut events, mut conn) = connect(&mut relay, &reconnecting()).await;
+
+    let conn = restart_connection(&mut relay, &mut conn, &mut events).await;
+
+    // The connection lost without a close frame is reconnected as well.
+    drop(conn);
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::InboundError(_)
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(None)
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Reconnecting { attempt: 1, .. }
+    ));
+
+    let mut conn = relay.accept().await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Reconnected { attempts: 1 }
+    ));
+
+    // The normal closure isn't reconnected.
+    conn.close(Some(CloseFrame {
+        code: CloseCode::Normal,
+        reason: Cow::Borrowed(""),
+    }))
+    .await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), events.next())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn unauthorized_close() {
+    let mut relay = Relay::new();
+    let policy = ReconnectPolicy::default()
+        .with_initial_delay(Duration::from_millis(10))
+        .with_close_codes([CloseCode::Restart, CloseCode::from(3000)]);
+    let (client, mut events, mut conn) =
+        connect(&mut relay, &options().with_reconnect_policy(policy)).await;
+
+    conn.close(unauthorized()).await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+    assert_eq!(*client.state().borrow(), ConnectionState::Disconnected);
+
+    // Reconnecting with the rejected auth token is pointless.
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), events.next())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn auth_refresh() {
+    let mut relay = Relay::new();
+    let options = reconnecting().with_auth_refresh(|| Ok(auth_token()));
+    let (_client, mut events, mut conn) = connect(&mut relay, &options).await;
+    let auth = conn.auth().unwrap().to_owned();
+
+    conn.close(unauthorized()).await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Reconnecting { attempt: 1, .. }
+    ));
+
+    let conn = relay.accept().await;
+    assert_ne!(conn.auth().unwrap(), auth);
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+}
@@ -0,0 +1,104 @@
+use {
+    common::{
+        connect,
+        eventually,
+        next_event,
+        reconnecting,
+        restart_connection,
+        subscription_id,
+        timeout,
+        Relay,
+    },
+    relay_client::websocket::ClientEvent,
+    relay_rpc::{
+        domain::{SubscriptionId, Topic},
+        rpc::Params,
+    },
+    serde_json::json,
+    std::collections::HashSet,
+};
+
+mod common;
+
+#[tokio::test]
+async fn resubscribe() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &reconnecting()).await;
+    let topics = [Topic::generate(), Topic::generate(), Topic::generate()];
+
+    let response = client.subscribe(topics[0].clone());
+    conn.handle(json!("sub0")).await;
+    assert_eq!(
+        timeout(response).await.unwrap(),
+        SubscriptionId::from("sub0")
+    );
+
+    let response = client.batch_subscribe(&topics[1..]);
+    conn.handle(json!(["sub1", "sub2"])).await;
+    timeout(response).await.unwrap();
+
+    assert_eq!(client.subscriptions().len(), 3);
+    assert_eq!(
+        subscription_id(&client, &topics[2]),
+        Some(SubscriptionId::from("sub2"))
+    );
+
+    let mut conn = restart_connection(&mut relay, &mut conn, &mut events).await;
+
+    // The subscriptions are restored with a batch request, and the registry is
+    // updated with the new IDs.
+    let request = conn.recv().await;
+    let Params::BatchSubscribe(batch) = request.params else {
+        panic!("unexpected request: {:?}", request.params);
+    };
+
+    assert_eq!(
+        batch.topics.iter().collect::<HashSet<_>>(),
+        topics.iter().collect::<HashSet<_>>()
+    );
+
+    let ids = batch
+        .topics
+        .iter()
+        .map(|topic| format!("new-{topic}"))
+        .collect::<Vec<_>>();
+    conn.respond(request.id, json!(ids)).await;
+
+    eventually(|| {
+        subscription_id(&client, &topics[0])
+            == Some(SubscriptionId::from(format!("new-{}", topics[0]).as_str()))
+    })
+    .await;
+
+    let response = client.unsubscribe(
+        topics[1].clone(),
+        subscription_id(&client, &topics[1]).unwrap(),
+    );
+    conn.handle(json!(true)).await;
+    timeout(response).await.unwrap();
+
+    assert_eq!(client.subscriptions().len(), 2);
+    assert_eq!(subscription_id(&client, &topics[1]), None);
+}
+
+#[tokio::test]
+async fn resubscribe_failed() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &reconnecting()).await;
+    let topic = Topic::generate();
+
+    let response = client.subscribe(topic.clone());
+    conn.handle(json!("sub")).await;
+    timeout(response).await.unwrap();
+
+    let mut conn = restart_connection(&mut relay, &mut conn, &mut events).await;
+
+    let request = conn.recv().await;
+    conn.respond_error(request.id, -32000, "subscribe failed")
+        .await;
+
+    match next_event(&mut events).await {
+        ClientEvent::ResubscribeFailed { topics, .. } => assert_eq!(topics, [topic]),
+        event => panic!("unexpected event: {event:?}"),
+    }
+}
@@ -0,0 +1,95 @@
+use {
+    common::{next_event, options, restart, timeout, Relay},
+    relay_client::{
+        error::Error,
+        websocket::{
+            ClientEvent,
+            ConnectionState,
+            EventChannel,
+            ReconnectPolicy,
+            RelayCloseCode,
+            WebsocketClientError,
+        },
+    },
+    relay_rpc::domain::Topic,
+    std::{borrow::Cow, time::Duration},
+    tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+};
+
+mod common;
+
+#[tokio::test]
+async fn connection_state() {
+    let mut relay = Relay::new();
+    let (client, mut events) = relay.builder().build_with_events(EventChannel::Bounded(16));
+    let state = client.state();
+
+    assert_eq!(*state.borrow(), ConnectionState::Disconnected);
+
+    // The delay leaves the time to observe the reconnecting state.
+    let policy = ReconnectPolicy::default()
+        .with_initial_delay(Duration::from_millis(200))
+        .with_jitter(0.0);
+    timeout(client.connect(&options().with_reconnect_policy(policy)))
+        .await
+        .unwrap();
+    let mut conn = relay.accept().await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+    assert_eq!(*state.borrow(), ConnectionState::Connected);
+
+    conn.close(restart()).await;
+
+    match next_event(&mut events).await {
+        ClientEvent::Disconnected(Some(frame)) => {
+            assert_eq!(RelayCloseCode::from(&frame), RelayCloseCode::ServerRestart)
+        }
+
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Reconnecting { attempt: 1, .. }
+    ));
+    assert_eq!(*state.borrow(), ConnectionState::Reconnecting);
+
+    let mut conn = relay.accept().await;
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Connected
+    ));
+    assert_eq!(*state.borrow(), ConnectionState::Connected);
+
+    // The pending requests fail with the decoded close code.
+    let response = client.subscribe(Topic::generate());
+    let _request = conn.recv().await;
+
+    conn.close(Some(CloseFrame {
+        code: CloseCode::from(3000),
+        reason: Cow::Borrowed("unauthorized"),
+    }))
+    .await;
+
+    match timeout(response).await {
+        Err(Error::WebsocketClient(WebsocketClientError::ConnectionClosed(reason))) => {
+            assert_eq!(reason.code(), Some(RelayCloseCode::Unauthorized))
+        }
+
+        result => panic!("unexpected result: {result:?}"),
+    }
+
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Reconnected { attempts: 1 }
+    ));
+    assert!(matches!(
+        next_event(&mut events).await,
+        ClientEvent::Disconnected(Some(_))
+    ));
+    assert_eq!(*state.borrow(), ConnectionState::Disconnected);
+}
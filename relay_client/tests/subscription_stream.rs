@@ -0,0 +1,131 @@
+use {
+    common::{
+        connect,
+        eventually,
+        next_event,
+        options,
+        reconnecting,
+        restart_connection,
+        subscription_id,
+        timeout,
+        Relay,
+    },
+    futures_util::StreamExt,
+    relay_client::websocket::ClientEvent,
+    relay_rpc::{
+        domain::{SubscriptionId, Topic},
+        rpc::Params,
+    },
+    serde_json::json,
+};
+
+mod common;
+
+#[tokio::test]
+async fn subscription_stream() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &reconnecting()).await;
+    let (topic, other) = (Topic::generate(), Topic::generate());
+
+    let (stream, _) = tokio::join!(
+        client.subscribe_stream(topic.clone()),
+        conn.handle(json!("sub"))
+    );
+    let mut stream = stream.unwrap();
+
+    conn.deliver(1, "sub", &topic, "stream").await;
+    conn.deliver(2, "other", &other, "handler").await;
+
+    assert_eq!(&*timeout(stream.next()).await.unwrap().message, "stream");
+
+    match next_event(&mut events).await {
+        ClientEvent::Message(message) => assert_eq!(&*message.message, "handler"),
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    let mut conn = restart_connection(&mut relay, &mut conn, &mut events).await;
+    conn.handle(json!(["sub-new"])).await;
+    eventually(|| subscription_id(&client, &topic) == Some(SubscriptionId::from("sub-new"))).await;
+
+    // Dropping the stream unsubscribes with the ID of the resubscription.
+    drop(stream);
+
+    let request = conn.handle(json!(true)).await;
+    let Params::Unsubscribe(unsubscribe) = request.params else {
+        panic!("unexpected request: {:?}", request.params);
+    };
+
+    assert_eq!(unsubscribe.topic, topic);
+    assert_eq!(unsubscribe.subscription_id, SubscriptionId::from("sub-new"));
+
+    eventually(|| client.subscriptions().is_empty()).await;
+}
+
+#[tokio::test]
+async fn subscribed_topic() {
+    let mut relay = Relay::new();
+    let (client, mut events, mut conn) = connect(&mut relay, &options()).await;
+    let topic = Topic::generate();
+
+    let response = client.subscribe(topic.clone());
+    conn.handle(json!("sub")).await;
+    timeout(response).await.unwrap();
+
+    let (stream, _) = tokio::join!(
+        client.subscribe_stream(topic.clone()),
+        conn.handle(json!("sub"))
+    );
+
+    // The topic stays subscribed, and its messages go back to the handler.
+    drop(stream.unwrap());
+
+    conn.deliver(1, "sub", &topic, "handler").await;
+
+    match next_event(&mut events).await {
+        ClientEvent::Message(message) => assert_eq!(&*message.message, "handler"),
+        event => panic!("unexpected event: {event:?}"),
+    }
+
+    conn.recv_response().await;
+
+    // Unsubscribing releases the topic.
+    let (stream, _) = tokio::join!(
+        client.subscribe_stream(topic.clone()),
+        conn.handle(json!("sub"))
+    );
+    let response = client.unsubscribe(topic.clone(), SubscriptionId::from("sub"));
+    conn.handle(json!(true)).await;
+    timeout(response).await.unwrap();
+
+    let response = client.subscribe(Topic::generate());
+    drop(stream.unwrap());
+
+    // The stream doesn't unsubscribe from a topic that isn't subscribed.
+    assert!(matches!(
+        conn.handle(json!("other")).await.params,
+        Params::Subscribe(_)
+    ));
+    timeout(response).await.unwrap();
+}
+
+#[tokio::test]
+async fn drop_outside_runtime() {
+    let mut relay = Relay::new();
+    let (client, _events, mut conn) = connect(&mut relay, &options()).await;
+    let topic = Topic::generate();
+
+    let (stream, _) = tokio::join!(
+        client.subscribe_stream(topic.clone()),
+        conn.handle(json!("sub"))
+    );
+    let stream = stream.unwrap();
+    eventually(|| subscription_id(&client, &topic).is_some()).await;
+
+    std::thread::spawn(move || drop(stream)).join().unwrap();
+
+    let Params::Unsubscribe(unsubscribe) = conn.handle(json!(true)).await.params else {
+        panic!("unexpected request");
+    };
+
+    assert_eq!(unsubscribe.topic, topic);
+}
@@ -0,0 +1,67 @@
+use {
+    common::{connect, options, timeout, Relay},
+    relay_client::error::Error,
+    relay_rpc::domain::{SubscriptionId, Topic},
+    serde_json::json,
+    std::time::Duration,
+};
+
+mod common;
+
+#[tokio::test]
+async fn request_timeout() {
+    let mut relay = Relay::new();
+    let options = options().with_request_timeout(Duration::from_millis(50));
+    let (client, _events, mut conn) = connect(&mut relay, &options).await;
+
+    let response = client.subscribe(Topic::generate());
+    let late = conn.recv().await;
+    assert!(matches!(timeout(response).await, Err(Error::Timeout)));
+
+    // The response to the timed out request is ignored.
+    conn.respond(late.id, json!("late")).await;
+
+    // The timeout of the request overrides the default one.
+    let response = client
+        .subscribe(Topic::generate())
+        .with_timeout(Duration::from_secs(5));
+    let request = conn.recv().await;
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    conn.respond(request.id, json!("sub")).await;
+
+    assert_eq!(
+        timeout(response).await.unwrap(),
+        SubscriptionId::from("sub")
+    );
+}
+
+#[tokio::test]
+async fn no_default_timeout() {
+    let mut relay = Relay::new();
+    let (client, _events, mut conn) = connect(&mut relay, &options()).await;
+
+    let response = client
+        .publish(
+            Topic::generate(),
+            "message",
+            0,
+            Duration::from_secs(300),
+            false,
+        )
+        .with_timeout(Duration::from_millis(50));
+    let _request = conn.recv().await;
+    assert!(matches!(timeout(response).await, Err(Error::Timeout)));
+
+    // Without a timeout, the request waits for the response.
+    let response = client.subscribe(Topic::generate());
+    let request = conn.recv().await;
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    conn.respond(request.id, json!("sub")).await;
+
+    assert_eq!(
+        timeout(response).await.unwrap(),
+        SubscriptionId::from("sub")
+    );
+}
@@ -13,6 +13,7 @@ pub use {chrono, ed25519_dalek, rand};
 #[cfg(feature = "cacao")]
 pub mod cacao;
 pub mod did;
+pub mod identity;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
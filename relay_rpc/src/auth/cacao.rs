@@ -1,14 +1,53 @@
 use {
-    self::{header::Header, payload::Payload, signature::Signature},
+    self::{
+        header::Header,
+        message::MessageError,
+        payload::Payload,
+        recap::{ReCap, ReCapError},
+        signature::Signature,
+    },
     core::fmt::Debug,
     serde::{Deserialize, Serialize},
     std::fmt::{Display, Write},
 };
 
+pub mod builder;
 pub mod header;
+pub mod message;
 pub mod payload;
+pub mod recap;
 pub mod signature;
 
+pub const EIP155_NAMESPACE: &str = "eip155";
+pub const SOLANA_NAMESPACE: &str = "solana";
+pub const COSMOS_NAMESPACE: &str = "cosmos";
+
+/// CAIP-2 namespaces and the chain names used in the CAIP-122 message
+/// preamble.
+const CHAIN_NAMES: [(&str, &str); 3] = [
+    (EIP155_NAMESPACE, Cacao::ETHEREUM),
+    (SOLANA_NAMESPACE, Cacao::SOLANA),
+    (COSMOS_NAMESPACE, Cacao::COSMOS),
+];
+
+/// Returns the chain name used in the CAIP-122 message for the CAIP-2
+/// namespace.
+pub fn chain_name(namespace: &str) -> Option<&'static str> {
+    CHAIN_NAMES
+        .iter()
+        .find(|(ns, _)| *ns == namespace)
+        .map(|(_, name)| *name)
+}
+
+/// Returns the CAIP-2 namespace for the chain name used in the CAIP-122
+/// message.
+pub fn chain_namespace(chain_name: &str) -> Option<&'static str> {
+    CHAIN_NAMES
+        .iter()
+        .find(|(_, name)| *name == chain_name)
+        .map(|(ns, _)| *ns)
+}
+
 /// Errors that can occur during Cacao verification.
 #[derive(Debug, thiserror::Error)]
 pub enum CacaoError {
@@ -21,11 +60,62 @@ pub enum CacaoError {
     #[error("Invalid payload resources")]
     PayloadResources,
 
+    #[error("Invalid payload resource URI: {0}")]
+    PayloadResourceUri(String),
+
+    #[error("Invalid payload domain")]
+    PayloadDomain,
+
+    #[error("Invalid payload audience URI")]
+    PayloadAudience,
+
+    #[error("Payload domain doesn't match the audience URI")]
+    PayloadDomainMismatch,
+
+    #[error("Invalid payload nonce")]
+    PayloadNonce,
+
+    #[error("Invalid payload issuer")]
+    PayloadIssuer,
+
+    #[error("Invalid payload timestamp: {0}")]
+    PayloadTimestamp(&'static str),
+
+    #[error("Payload is issued in the future")]
+    PayloadIssuedInFuture,
+
+    #[error("Payload is expired")]
+    PayloadExpired,
+
+    #[error("Payload is not yet valid")]
+    PayloadNotYetValid,
+
+    #[error("Invalid payload ReCap: {0}")]
+    PayloadReCap(#[from] ReCapError),
+
+    #[error("Payload ReCaps must be the last resources")]
+    PayloadReCapPosition,
+
+    #[error("Payload statement doesn't match the ReCaps")]
+    PayloadReCapStatement,
+
     #[error("Unsupported signature type")]
     UnsupportedSignature,
 
+    #[error("Unsupported chain namespace: {0}")]
+    UnsupportedNamespace(String),
+
     #[error("Unable to verify")]
     Verification,
+
+    #[error("Failed to sign")]
+    Signing,
+
+    #[error("EIP-1271 verification failed: {0}")]
+    Eip1271(String),
+
+    #[error("Invalid message: {0}")]
+    Message(#[from] MessageError),
 }
 
 impl From<std::fmt::Error> for CacaoError {
@@ -75,7 +165,9 @@ pub struct Cacao {
 }
 
 impl Cacao {
+    const COSMOS: &'static str = "Cosmos";
     const ETHEREUM: &'static str = "Ethereum";
+    const SOLANA: &'static str = "Solana";
 
     pub fn verify(&self) -> Result<bool, CacaoError> {
         self.p.validate()?;
@@ -83,10 +175,43 @@ impl Cacao {
         self.s.verify(self)
     }
 
+    /// Same as [`Cacao::verify()`], but also supports the signature types that
+    /// require access to the blockchain, such as EIP-1271 smart contract
+    /// wallet signatures.
+    #[cfg(feature = "eip1271")]
+    pub async fn verify_with(&self, verifiers: &signature::Verifiers) -> Result<bool, CacaoError> {
+        self.p.validate()?;
+        self.h.validate()?;
+        self.s.verify_with(self, verifiers).await
+    }
+
+    /// Returns the capabilities granted by the ReCaps in the payload
+    /// resources. Invalid ReCaps are ignored here, they're rejected by
+    /// [`Cacao::verify()`].
+    pub fn capabilities(&self) -> ReCap {
+        let mut capabilities = ReCap::new();
+
+        for recap in self.p.recaps().filter_map(Result::ok) {
+            capabilities.merge(recap);
+        }
+
+        capabilities
+    }
+
     pub fn siwe_message(&self) -> Result<String, CacaoError> {
         self.caip122_message(Self::ETHEREUM)
     }
 
+    /// Renders the CAIP-122 message, using the chain name for the namespace of
+    /// the `iss`, e.g. "Solana" for `did:pkh:solana:...`.
+    pub fn message(&self) -> Result<String, CacaoError> {
+        let namespace = self.p.namespace()?;
+        let chain_name =
+            chain_name(&namespace).ok_or(CacaoError::UnsupportedNamespace(namespace))?;
+
+        self.caip122_message(chain_name)
+    }
+
     pub fn caip122_message(&self, chain_name: &str) -> Result<String, CacaoError> {
         let mut message = format!(
             "{} wants you to sign in with your {} account:\n{}\n\n",
@@ -0,0 +1,166 @@
+use {
+    super::{
+        header::{Header, EIP4361},
+        payload::Payload,
+        recap::ReCap,
+        signature::{eip155_address, Eip191, Signature, EIP191},
+        Cacao,
+        CacaoError,
+        Version,
+        EIP155_NAMESPACE,
+    },
+    crate::auth::did::{combine_did_data, DID_METHOD_PKH},
+    chrono::{DateTime, SecondsFormat, Utc},
+    k256::ecdsa::SigningKey,
+};
+
+/// Builder for creating Cacaos signed with a secp256k1 key using EIP-191, i.e.
+/// the way Ethereum wallets sign in with SIWE (EIP-4361).
+#[derive(Debug, Clone)]
+pub struct CacaoBuilder {
+    domain: String,
+    aud: String,
+    chain_id: String,
+    statement: Option<String>,
+    nonce: Option<String>,
+    iat: Option<DateTime<Utc>>,
+    exp: Option<DateTime<Utc>>,
+    nbf: Option<DateTime<Utc>>,
+    request_id: Option<String>,
+    resources: Vec<String>,
+    recap: Option<ReCap>,
+}
+
+impl CacaoBuilder {
+    /// Creates a new builder for the `domain` (RFC 3986 authority) requesting
+    /// the signing, and the `aud` URI referring to the subject of the signing.
+    pub fn new(domain: impl Into<String>, aud: impl Into<String>) -> Self {
+        Self {
+            domain: domain.into(),
+            aud: aud.into(),
+            chain_id: "1".to_owned(),
+            statement: None,
+            nonce: None,
+            iat: None,
+            exp: None,
+            nbf: None,
+            request_id: None,
+            resources: Vec::new(),
+            recap: None,
+        }
+    }
+
+    /// EIP-155 chain ID. Defaults to `1` (Ethereum mainnet).
+    pub fn chain_id(mut self, chain_id: impl Into<String>) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+
+    pub fn statement(mut self, statement: impl Into<Option<String>>) -> Self {
+        self.statement = statement.into();
+        self
+    }
+
+    /// Nonce to prevent replay attacks. A random nonce is generated if not
+    /// set.
+    pub fn nonce(mut self, nonce: impl Into<Option<String>>) -> Self {
+        self.nonce = nonce.into();
+        self
+    }
+
+    /// Issued at timestamp. Defaults to the current time.
+    pub fn iat(mut self, iat: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.iat = iat.into();
+        self
+    }
+
+    pub fn exp(mut self, exp: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.exp = exp.into();
+        self
+    }
+
+    pub fn nbf(mut self, nbf: impl Into<Option<DateTime<Utc>>>) -> Self {
+        self.nbf = nbf.into();
+        self
+    }
+
+    pub fn request_id(mut self, request_id: impl Into<Option<String>>) -> Self {
+        self.request_id = request_id.into();
+        self
+    }
+
+    /// Adds a resource URI.
+    pub fn resource(mut self, resource: impl Into<String>) -> Self {
+        self.resources.push(resource.into());
+        self
+    }
+
+    pub fn resources(mut self, resources: impl IntoIterator<Item = String>) -> Self {
+        self.resources.extend(resources);
+        self
+    }
+
+    /// Grants the ReCap (EIP-5573) capabilities. The ReCap is added as the last
+    /// resource, and its text is appended to the statement. Multiple ReCaps
+    /// are merged.
+    pub fn recap(mut self, recap: ReCap) -> Self {
+        match &mut self.recap {
+            Some(existing) => existing.merge(recap),
+            None => self.recap = Some(recap),
+        }
+        self
+    }
+
+    /// Creates the payload for the provided EIP-55 address.
+    pub fn build_payload(&self, address: &str) -> Result<Payload, CacaoError> {
+        let account = format!("{EIP155_NAMESPACE}:{}:{address}", self.chain_id);
+        let mut statement = self.statement.clone();
+        let mut resources = self.resources.clone();
+
+        if let Some(recap) = self.recap.as_ref().filter(|recap| !recap.is_empty()) {
+            statement = Some(recap.append_to_statement(statement.as_deref()));
+            resources.push(recap.encode()?);
+        }
+
+        Ok(Payload {
+            domain: self.domain.clone(),
+            iss: combine_did_data(DID_METHOD_PKH, &account),
+            statement,
+            aud: self.aud.clone(),
+            version: Version::V1,
+            nonce: self.nonce.clone().unwrap_or_else(generate_nonce),
+            iat: format_timestamp(self.iat.unwrap_or_else(Utc::now)),
+            exp: self.exp.map(format_timestamp),
+            nbf: self.nbf.map(format_timestamp),
+            request_id: self.request_id.clone(),
+            resources: (!resources.is_empty()).then_some(resources),
+        })
+    }
+
+    /// Creates the payload, renders the SIWE message and signs it with the
+    /// provided key.
+    pub fn sign(&self, key: &SigningKey) -> Result<Cacao, CacaoError> {
+        let mut cacao = Cacao {
+            h: Header {
+                t: EIP4361.to_owned(),
+            },
+            p: self.build_payload(&eip155_address(key.verifying_key()))?,
+            s: Signature {
+                t: EIP191.to_owned(),
+                s: String::new(),
+            },
+        };
+
+        cacao.s.s = Eip191.sign(key, &cacao.siwe_message()?)?;
+
+        Ok(cacao)
+    }
+}
+
+fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn generate_nonce() -> String {
+    data_encoding::HEXLOWER.encode(&rand::random::<[u8; 16]>())
+}
@@ -0,0 +1,310 @@
+use {
+    super::{
+        chain_namespace,
+        payload::{Payload, AUTHORITY, CAIP10_ACCOUNT, URI},
+        signature::eip55_checksum,
+        Version,
+        EIP155_NAMESPACE,
+    },
+    crate::auth::did::{combine_did_data, DID_METHOD_PKH},
+    chrono::DateTime,
+    once_cell::sync::Lazy,
+    regex::Regex,
+    std::{iter::Peekable, str::Split},
+};
+
+const PREAMBLE_DELIMITER: &str = " wants you to sign in with your ";
+const PREAMBLE_SUFFIX: &str = " account:";
+const FIELD_DELIMITER: &str = ": ";
+const RESOURCE_PREFIX: &str = "- ";
+
+const URI_FIELD: &str = "URI";
+const VERSION_FIELD: &str = "Version";
+const CHAIN_ID_FIELD: &str = "Chain ID";
+const NONCE_FIELD: &str = "Nonce";
+const ISSUED_AT_FIELD: &str = "Issued At";
+const EXPIRATION_TIME_FIELD: &str = "Expiration Time";
+const NOT_BEFORE_FIELD: &str = "Not Before";
+const REQUEST_ID_FIELD: &str = "Request ID";
+const RESOURCES_FIELD: &str = "Resources:";
+
+/// EIP-155 chain ID.
+static EIP155_CHAIN_ID: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[1-9][0-9]*$").unwrap());
+
+/// EIP-4361 nonce: at least 8 alphanumeric characters.
+static SIWE_NONCE: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[a-zA-Z0-9]{8,}$").unwrap());
+
+/// Errors that can occur while parsing a CAIP-122 message. Line numbers are
+/// 1-based.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MessageError {
+    #[error("Unexpected end of message, expected {0}")]
+    UnexpectedEnd(&'static str),
+
+    #[error("Line {0}: invalid preamble")]
+    Preamble(usize),
+
+    #[error("Line {line}: unsupported chain: {chain}")]
+    UnsupportedChain { line: usize, chain: String },
+
+    #[error("Line {0}: invalid domain")]
+    Domain(usize),
+
+    #[error("Line {0}: invalid address")]
+    Address(usize),
+
+    #[error("Line {0}: expected an empty line")]
+    ExpectedEmptyLine(usize),
+
+    #[error("Line {line}: expected `{field}` field")]
+    MissingField { line: usize, field: &'static str },
+
+    #[error("Line {line}: invalid `{field}` value")]
+    InvalidValue { line: usize, field: &'static str },
+
+    #[error("Line {0}: unexpected content")]
+    UnexpectedLine(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Generic CAIP-122 message. Only the message structure is checked.
+    Caip122,
+
+    /// EIP-4361 (Sign-In with Ethereum) message. In addition to the structure,
+    /// the field values are checked against the EIP-4361 ABNF.
+    Siwe,
+}
+
+impl Payload {
+    /// Parses the CAIP-122 message text, as rendered by
+    /// [`Cacao::caip122_message()`](super::Cacao::caip122_message), back into
+    /// a payload. The `iss` is
+    /// reconstructed from the chain name, chain ID and address.
+    ///
+    /// Only the message structure and the CAIP-10 account are checked here. Use
+    /// [`Payload::validate()`] to validate the resulting payload.
+    pub fn from_caip122_message(message: &str) -> Result<Self, MessageError> {
+        parse(message, Mode::Caip122)
+    }
+
+    /// Same as [`Payload::from_caip122_message()`], but strictly follows
+    /// EIP-4361: the chain must be Ethereum, the address must be EIP-55
+    /// checksummed, the nonce must be at least 8 alphanumeric characters, and
+    /// the URIs and timestamps must be well-formed.
+    pub fn from_siwe_message(message: &str) -> Result<Self, MessageError> {
+        parse(message, Mode::Siwe)
+    }
+}
+
+fn parse(message: &str, mode: Mode) -> Result<Payload, MessageError> {
+    let strict = mode == Mode::Siwe;
+    let mut lines = Lines::new(message);
+
+    let (line, preamble) = lines.next("preamble")?;
+    let (domain, chain_name) = preamble
+        .strip_suffix(PREAMBLE_SUFFIX)
+        .and_then(|preamble| preamble.split_once(PREAMBLE_DELIMITER))
+        .ok_or(MessageError::Preamble(line))?;
+
+    if !AUTHORITY.is_match(domain) {
+        return Err(MessageError::Domain(line));
+    }
+
+    let namespace = chain_namespace(chain_name)
+        .filter(|namespace| !strict || *namespace == EIP155_NAMESPACE)
+        .ok_or_else(|| MessageError::UnsupportedChain {
+            line,
+            chain: chain_name.to_owned(),
+        })?;
+
+    let (address_line, address) = lines.next("address")?;
+    let address_valid = !strict
+        || address.len() == 42 && address.starts_with("0x") && eip55_checksum(address) == address;
+
+    if !address_valid {
+        return Err(MessageError::Address(address_line));
+    }
+
+    lines.empty()?;
+
+    let (line, statement) = lines.next("statement or empty line")?;
+    let statement = if statement.is_empty() {
+        None
+    } else if field_value(statement, URI_FIELD).is_some() {
+        // The statement is optional, but the empty line before the fields is
+        // not.
+        return Err(MessageError::ExpectedEmptyLine(line));
+    } else {
+        lines.empty()?;
+        Some(statement.to_owned())
+    };
+
+    let (line, aud) = lines.field(URI_FIELD)?;
+    check(line, URI_FIELD, !strict || URI.is_match(aud))?;
+
+    let (line, version) = lines.field(VERSION_FIELD)?;
+    let version = match version {
+        "1" => Version::V1,
+        _ => {
+            return Err(MessageError::InvalidValue {
+                line,
+                field: VERSION_FIELD,
+            })
+        }
+    };
+
+    let (line, chain_id) = lines.field(CHAIN_ID_FIELD)?;
+    check(
+        line,
+        CHAIN_ID_FIELD,
+        !strict || EIP155_CHAIN_ID.is_match(chain_id),
+    )?;
+
+    // The address is only checked as a part of the CAIP-10 account in the
+    // generic mode.
+    let account = format!("{namespace}:{chain_id}:{address}");
+    if !CAIP10_ACCOUNT.is_match(&account) {
+        return Err(MessageError::Address(address_line));
+    }
+
+    let (line, nonce) = lines.field(NONCE_FIELD)?;
+    check(line, NONCE_FIELD, !strict || SIWE_NONCE.is_match(nonce))?;
+
+    let (line, iat) = lines.field(ISSUED_AT_FIELD)?;
+    check(line, ISSUED_AT_FIELD, !strict || is_timestamp(iat))?;
+
+    let exp = lines.optional_field(EXPIRATION_TIME_FIELD);
+    if let Some((line, exp)) = exp {
+        check(line, EXPIRATION_TIME_FIELD, !strict || is_timestamp(exp))?;
+    }
+
+    let nbf = lines.optional_field(NOT_BEFORE_FIELD);
+    if let Some((line, nbf)) = nbf {
+        check(line, NOT_BEFORE_FIELD, !strict || is_timestamp(nbf))?;
+    }
+
+    let request_id = lines.optional_field(REQUEST_ID_FIELD);
+    if let Some((line, request_id)) = request_id {
+        check(line, REQUEST_ID_FIELD, !request_id.is_empty())?;
+    }
+
+    let resources = if lines.optional_tag(RESOURCES_FIELD) {
+        let header_line = lines.line;
+        let mut resources = Vec::new();
+
+        while let Some((line, resource)) = lines.optional_tag_value(RESOURCE_PREFIX) {
+            check(
+                line,
+                RESOURCES_FIELD,
+                !resource.is_empty() && (!strict || URI.is_match(resource)),
+            )?;
+            resources.push(resource.to_owned());
+        }
+
+        // The header is only rendered along with the resources, so the message
+        // wouldn't round-trip without any.
+        check(header_line, RESOURCES_FIELD, !resources.is_empty())?;
+
+        Some(resources)
+    } else {
+        None
+    };
+
+    lines.end()?;
+
+    Ok(Payload {
+        domain: domain.to_owned(),
+        iss: combine_did_data(DID_METHOD_PKH, &account),
+        statement,
+        aud: aud.to_owned(),
+        version,
+        nonce: nonce.to_owned(),
+        iat: iat.to_owned(),
+        exp: exp.map(|(_, exp)| exp.to_owned()),
+        nbf: nbf.map(|(_, nbf)| nbf.to_owned()),
+        request_id: request_id.map(|(_, request_id)| request_id.to_owned()),
+        resources,
+    })
+}
+
+fn check(line: usize, field: &'static str, valid: bool) -> Result<(), MessageError> {
+    if valid {
+        Ok(())
+    } else {
+        Err(MessageError::InvalidValue { line, field })
+    }
+}
+
+fn is_timestamp(value: &str) -> bool {
+    DateTime::parse_from_rfc3339(value).is_ok()
+}
+
+/// Cursor over the message lines, keeping track of the line numbers.
+struct Lines<'a> {
+    inner: Peekable<Split<'a, char>>,
+    line: usize,
+}
+
+impl<'a> Lines<'a> {
+    fn new(message: &'a str) -> Self {
+        Self {
+            inner: message.split('\n').peekable(),
+            line: 0,
+        }
+    }
+
+    fn next(&mut self, expected: &'static str) -> Result<(usize, &'a str), MessageError> {
+        let text = self
+            .inner
+            .next()
+            .ok_or(MessageError::UnexpectedEnd(expected))?;
+        self.line += 1;
+
+        Ok((self.line, text))
+    }
+
+    fn empty(&mut self) -> Result<(), MessageError> {
+        match self.next("empty line")? {
+            (_, "") => Ok(()),
+            (line, _) => Err(MessageError::ExpectedEmptyLine(line)),
+        }
+    }
+
+    fn field(&mut self, field: &'static str) -> Result<(usize, &'a str), MessageError> {
+        let (line, text) = self.next(field)?;
+        let value = field_value(text, field).ok_or(MessageError::MissingField { line, field })?;
+        check(line, field, !value.is_empty())?;
+
+        Ok((line, value))
+    }
+
+    fn optional_field(&mut self, field: &'static str) -> Option<(usize, &'a str)> {
+        let value = field_value(self.inner.peek()?, field)?;
+        self.next(field).ok()?;
+
+        Some((self.line, value))
+    }
+
+    fn optional_tag(&mut self, tag: &'static str) -> bool {
+        self.inner.peek() == Some(&tag) && self.next(tag).is_ok()
+    }
+
+    fn optional_tag_value(&mut self, prefix: &'static str) -> Option<(usize, &'a str)> {
+        let value = self.inner.peek()?.strip_prefix(prefix)?;
+        self.next(prefix).ok()?;
+
+        Some((self.line, value))
+    }
+
+    fn end(&mut self) -> Result<(), MessageError> {
+        match self.inner.next() {
+            Some(_) => Err(MessageError::UnexpectedLine(self.line + 1)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn field_value<'a>(text: &'a str, field: &str) -> Option<&'a str> {
+    text.strip_prefix(field)?.strip_prefix(FIELD_DELIMITER)
+}
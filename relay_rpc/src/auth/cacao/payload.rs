@@ -1,9 +1,75 @@
 use {
-    super::{CacaoError, Version},
-    crate::auth::did::{extract_did_data, DID_METHOD_KEY},
+    super::{recap::ReCap, CacaoError, Version},
+    crate::auth::did::{extract_did_data, DID_METHOD_KEY, DID_METHOD_PKH},
+    chrono::{DateTime, Utc},
+    once_cell::sync::Lazy,
+    regex::Regex,
     serde::{Deserialize, Serialize},
+    std::{fmt::Debug, sync::Arc},
 };
 
+/// The default allowed clock skew when validating the payload timestamps.
+pub const CACAO_VALIDATION_TIME_LEEWAY_SECS: i64 = 120;
+
+/// RFC 3986 authority: `[userinfo@]host[:port]`.
+pub(super) static AUTHORITY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z0-9\-._~%!$&'()*+,;=:@\[\]]+$").unwrap());
+
+/// RFC 3986 absolute URI, capturing the authority if present.
+pub(super) static URI: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[A-Za-z][A-Za-z0-9+\-.]*:(//([^/?#]*))?[A-Za-z0-9\-._~%!$&'()*+,;=:@/?#\[\]]*$")
+        .unwrap()
+});
+
+/// RFC 3986 URI reference, which may also be relative.
+static URI_REFERENCE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z0-9\-._~%!$&'()*+,;=:@/?#\[\]]+$").unwrap());
+
+/// CAIP-10 account ID: `namespace:reference:address`.
+pub(super) static CAIP10_ACCOUNT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^[-a-z0-9]{3,8}:[-_a-zA-Z0-9]{1,32}:[-.%a-zA-Z0-9]{1,128}$").unwrap()
+});
+
+/// Options for the [`Payload`] validation.
+#[derive(Clone)]
+pub struct ValidationOptions {
+    /// Allowed clock skew, in seconds, when validating the `iat`, `exp` and
+    /// `nbf` timestamps.
+    pub time_leeway: i64,
+
+    /// Source of the current time.
+    pub clock: Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+}
+
+impl ValidationOptions {
+    pub fn with_time_leeway(mut self, time_leeway: i64) -> Self {
+        self.time_leeway = time_leeway;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            time_leeway: CACAO_VALIDATION_TIME_LEEWAY_SECS,
+            clock: Arc::new(Utc::now),
+        }
+    }
+}
+
+impl Debug for ValidationOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValidationOptions")
+            .field("time_leeway", &self.time_leeway)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Hash)]
 pub struct Payload {
     pub domain: String,
@@ -25,8 +91,141 @@ impl Payload {
     const ISS_POSITION_OF_NAMESPACE: usize = 2;
     const ISS_POSITION_OF_REFERENCE: usize = 3;
 
-    /// TODO: write valdation
+    /// Validates the payload using the system clock and the default time
+    /// leeway. See [`Payload::validate_with()`].
     pub fn validate(&self) -> Result<(), CacaoError> {
+        self.validate_with(&ValidationOptions::default())
+    }
+
+    /// Validates the payload. This includes the following checks:
+    /// - `domain` is a valid RFC 3986 authority, and `aud` is a valid URI with
+    ///   the same host, if it has one;
+    /// - `nonce` is not empty;
+    /// - `iss` is a valid `did:pkh`;
+    /// - `iat`, `exp` and `nbf` are valid RFC 3339 timestamps, the payload is
+    ///   not issued in the future, not expired and already valid (with the
+    ///   configured leeway);
+    /// - `resources` are valid RFC 3986 URI references;
+    /// - ReCaps in `resources` are valid and come last, and the statement ends
+    ///   with the text generated for them.
+    ///
+    /// Note: The payload version is checked during deserialization, since
+    /// [`Version`] only represents the supported versions.
+    pub fn validate_with(&self, options: &ValidationOptions) -> Result<(), CacaoError> {
+        self.validate_domain()?;
+
+        if self.nonce.trim().is_empty() {
+            return Err(CacaoError::PayloadNonce);
+        }
+
+        let account =
+            extract_did_data(&self.iss, DID_METHOD_PKH).map_err(|_| CacaoError::PayloadIssuer)?;
+
+        if !CAIP10_ACCOUNT.is_match(account) {
+            return Err(CacaoError::PayloadIssuer);
+        }
+
+        self.validate_timestamps(options)?;
+
+        for resource in self.resources.iter().flatten() {
+            if !URI_REFERENCE.is_match(resource) {
+                return Err(CacaoError::PayloadResourceUri(resource.clone()));
+            }
+        }
+
+        self.validate_recaps()
+    }
+
+    fn validate_recaps(&self) -> Result<(), CacaoError> {
+        let resources = self.resources.as_deref().unwrap_or_default();
+        let first_recap = resources
+            .iter()
+            .position(|resource| ReCap::is_recap_uri(resource))
+            .unwrap_or(resources.len());
+
+        if !resources[first_recap..]
+            .iter()
+            .all(|resource| ReCap::is_recap_uri(resource))
+        {
+            return Err(CacaoError::PayloadReCapPosition);
+        }
+
+        let mut capabilities = ReCap::new();
+
+        for recap in self.recaps() {
+            capabilities.merge(recap?);
+        }
+
+        if !capabilities.is_empty() && !capabilities.matches_statement(self.statement.as_deref()) {
+            return Err(CacaoError::PayloadReCapStatement);
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the ReCaps (EIP-5573) in the resources.
+    pub fn recaps(&self) -> impl Iterator<Item = Result<ReCap, CacaoError>> + '_ {
+        self.resources
+            .iter()
+            .flatten()
+            .filter(|resource| ReCap::is_recap_uri(resource))
+            .map(|resource| ReCap::decode(resource).map_err(Into::into))
+    }
+
+    fn validate_domain(&self) -> Result<(), CacaoError> {
+        if !AUTHORITY.is_match(&self.domain) {
+            return Err(CacaoError::PayloadDomain);
+        }
+
+        let aud = URI.captures(&self.aud).ok_or(CacaoError::PayloadAudience)?;
+
+        // Audience may be a URI without an authority, e.g. a `did:key`.
+        if let Some(authority) = aud.get(2) {
+            let (aud_host, aud_port) = split_authority(authority.as_str());
+            let (host, port) = split_authority(&self.domain);
+
+            let port_matches = match (port, aud_port) {
+                (Some(port), Some(aud_port)) => port == aud_port,
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+
+            if !host.eq_ignore_ascii_case(aud_host) || !port_matches {
+                return Err(CacaoError::PayloadDomainMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_timestamps(&self, options: &ValidationOptions) -> Result<(), CacaoError> {
+        let now = (options.clock)().timestamp();
+        let time_leeway = options.time_leeway;
+
+        let iat = parse_timestamp(&self.iat, "iat")?;
+        let exp = self
+            .exp
+            .as_deref()
+            .map(|exp| parse_timestamp(exp, "exp"))
+            .transpose()?;
+        let nbf = self
+            .nbf
+            .as_deref()
+            .map(|nbf| parse_timestamp(nbf, "nbf"))
+            .transpose()?;
+
+        if now + time_leeway < iat {
+            return Err(CacaoError::PayloadIssuedInFuture);
+        }
+
+        if matches!(exp, Some(exp) if now - time_leeway > exp) {
+            return Err(CacaoError::PayloadExpired);
+        }
+
+        if matches!(nbf, Some(nbf) if now + time_leeway < nbf) {
+            return Err(CacaoError::PayloadNotYetValid);
+        }
+
         Ok(())
     }
 
@@ -83,7 +282,10 @@ impl Payload {
             .resources
             .as_ref()
             .ok_or(CacaoError::PayloadResources)?;
-        let did_key = resources.first().ok_or(CacaoError::PayloadIdentityKey)?;
+        let did_key = resources
+            .iter()
+            .find(|resource| !ReCap::is_recap_uri(resource))
+            .ok_or(CacaoError::PayloadIdentityKey)?;
 
         extract_did_data(did_key, DID_METHOD_KEY)
             .map(|data| data.to_string())
@@ -96,3 +298,23 @@ impl Payload {
             .map_err(|_| CacaoError::PayloadIdentityKey)
     }
 }
+
+fn parse_timestamp(value: &str, field: &'static str) -> Result<i64, CacaoError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.timestamp())
+        .map_err(|_| CacaoError::PayloadTimestamp(field))
+}
+
+/// Splits the RFC 3986 authority into host and optional port, dropping the
+/// user info.
+fn split_authority(authority: &str) -> (&str, Option<&str>) {
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host_port)| host_port);
+
+    // Don't confuse the IPv6 address delimiters with the port delimiter.
+    match host_port.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (host_port, None),
+    }
+}
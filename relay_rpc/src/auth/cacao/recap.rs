@@ -0,0 +1,243 @@
+use {
+    super::payload::URI,
+    serde::{Deserialize, Serialize},
+    serde_json::{Map, Value},
+    std::collections::BTreeMap,
+};
+
+pub const RECAP_URI_PREFIX: &str = "urn:recap:";
+
+const ABILITY_DELIMITER: char = '/';
+const STATEMENT_PREFIX: &str =
+    "I further authorize the stated URI to perform the following actions on my behalf:";
+
+/// Errors that can occur while decoding or encoding ReCaps.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ReCapError {
+    #[error("Not a ReCap URI")]
+    Prefix,
+
+    #[error("Invalid base64url encoding")]
+    Encoding,
+
+    #[error("Invalid ReCap JSON: {0}")]
+    Json(String),
+
+    #[error("Invalid resource URI: {0}")]
+    Resource(String),
+
+    #[error("Invalid ability: {0}")]
+    Ability(String),
+}
+
+/// Restrictions attached to an ability. An empty object means no restrictions.
+pub type Note = Map<String, Value>;
+
+/// ReCap capability object (EIP-5573), encoded in the SIWE resources as
+/// `urn:recap:<base64url JSON>`.
+///
+/// Resources and abilities are kept sorted, so that the encoding and the
+/// generated statement are deterministic.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReCap {
+    /// Attenuations: resource URI -> `namespace/name` ability -> notes.
+    pub att: BTreeMap<String, BTreeMap<String, Vec<Note>>>,
+
+    /// CIDs of the parent capabilities.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prf: Vec<String>,
+}
+
+impl ReCap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants the `namespace/name` ability on the resource URI without any
+    /// restrictions.
+    pub fn ability(self, resource: impl Into<String>, ability: impl Into<String>) -> Self {
+        self.ability_with_notes(resource, ability, [Note::new()])
+    }
+
+    /// Grants the `namespace/name` ability on the resource URI with the
+    /// provided restrictions.
+    pub fn ability_with_notes(
+        mut self,
+        resource: impl Into<String>,
+        ability: impl Into<String>,
+        notes: impl IntoIterator<Item = Note>,
+    ) -> Self {
+        self.att
+            .entry(resource.into())
+            .or_default()
+            .entry(ability.into())
+            .or_default()
+            .extend(notes);
+        self
+    }
+
+    pub fn is_recap_uri(uri: &str) -> bool {
+        uri.starts_with(RECAP_URI_PREFIX)
+    }
+
+    /// Decodes and validates the `urn:recap:` URI.
+    pub fn decode(uri: &str) -> Result<Self, ReCapError> {
+        let encoded = uri
+            .strip_prefix(RECAP_URI_PREFIX)
+            .ok_or(ReCapError::Prefix)?
+            .trim_end_matches('=');
+        let json = data_encoding::BASE64URL_NOPAD
+            .decode(encoded.as_bytes())
+            .map_err(|_| ReCapError::Encoding)?;
+        let recap: Self =
+            serde_json::from_slice(&json).map_err(|err| ReCapError::Json(err.to_string()))?;
+
+        recap.validate()?;
+
+        Ok(recap)
+    }
+
+    /// Encodes the capability as a `urn:recap:` URI.
+    pub fn encode(&self) -> Result<String, ReCapError> {
+        self.validate()?;
+
+        let json = serde_json::to_vec(self).map_err(|err| ReCapError::Json(err.to_string()))?;
+
+        Ok(format!(
+            "{RECAP_URI_PREFIX}{}",
+            data_encoding::BASE64URL_NOPAD.encode(&json)
+        ))
+    }
+
+    pub fn validate(&self) -> Result<(), ReCapError> {
+        for (resource, abilities) in &self.att {
+            if !URI.is_match(resource) {
+                return Err(ReCapError::Resource(resource.clone()));
+            }
+
+            for ability in abilities.keys() {
+                split_ability(ability).ok_or_else(|| ReCapError::Ability(ability.clone()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merges the other capability into this one. Abilities on the same
+    /// resource are combined, as are their notes and proofs.
+    pub fn merge(&mut self, other: ReCap) {
+        for (resource, abilities) in other.att {
+            let target = self.att.entry(resource).or_default();
+
+            for (ability, notes) in abilities {
+                let target = target.entry(ability).or_default();
+
+                for note in notes {
+                    if !target.contains(&note) {
+                        target.push(note);
+                    }
+                }
+            }
+        }
+
+        for proof in other.prf {
+            if !self.prf.contains(&proof) {
+                self.prf.push(proof);
+            }
+        }
+    }
+
+    /// Checks whether the `namespace/name` ability is granted on the resource
+    /// URI.
+    pub fn allows(&self, resource: &str, ability: &str) -> bool {
+        self.att
+            .get(resource)
+            .is_some_and(|abilities| abilities.contains_key(ability))
+    }
+
+    /// Returns the restrictions of the ability on the resource URI, if it's
+    /// granted.
+    pub fn notes(&self, resource: &str, ability: &str) -> Option<&[Note]> {
+        self.att
+            .get(resource)?
+            .get(ability)
+            .map(|notes| notes.as_slice())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.att.is_empty()
+    }
+
+    /// Generates the human-readable text that must be appended to the SIWE
+    /// statement, e.g.:
+    ///
+    /// `I further authorize the stated URI to perform the following actions on
+    /// my behalf: (1) 'crud': 'delete', 'update' for 'https://example.com'.`
+    pub fn statement(&self) -> String {
+        let mut statement = STATEMENT_PREFIX.to_owned();
+        let mut index = 0;
+
+        for (resource, abilities) in &self.att {
+            // Group the ability names by namespace.
+            let mut namespaces = BTreeMap::<&str, Vec<&str>>::new();
+
+            for (namespace, name) in abilities
+                .keys()
+                .filter_map(|ability| split_ability(ability))
+            {
+                namespaces.entry(namespace).or_default().push(name);
+            }
+
+            for (namespace, mut names) in namespaces {
+                names.sort_unstable();
+                index += 1;
+
+                let names = names
+                    .iter()
+                    .map(|name| format!("'{name}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                statement.push_str(&format!(
+                    " ({index}) '{namespace}': {names} for '{resource}'."
+                ));
+            }
+        }
+
+        statement
+    }
+
+    /// Checks whether the SIWE statement ends with the text generated for this
+    /// capability.
+    pub fn matches_statement(&self, statement: Option<&str>) -> bool {
+        let expected = self.statement();
+
+        match statement {
+            Some(statement) => {
+                statement == expected
+                    || statement
+                        .strip_suffix(&expected)
+                        .is_some_and(|prefix| prefix.ends_with(' '))
+            }
+            None => false,
+        }
+    }
+
+    /// Appends the generated text to the SIWE statement.
+    pub fn append_to_statement(&self, statement: Option<&str>) -> String {
+        match statement {
+            Some(statement) if !statement.is_empty() => {
+                format!("{statement} {}", self.statement())
+            }
+            _ => self.statement(),
+        }
+    }
+}
+
+fn split_ability(ability: &str) -> Option<(&str, &str)> {
+    ability
+        .split_once(ABILITY_DELIMITER)
+        .filter(|(namespace, name)| {
+            !namespace.is_empty() && !name.is_empty() && !name.contains(ABILITY_DELIMITER)
+        })
+}
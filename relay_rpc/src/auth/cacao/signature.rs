@@ -1,7 +1,21 @@
+#[cfg(feature = "eip1271")]
+pub use eip1271::*;
 use {
     super::{Cacao, CacaoError},
+    k256::ecdsa::{SigningKey, VerifyingKey},
+    once_cell::sync::Lazy,
     serde::{Deserialize, Serialize},
+    std::{collections::HashMap, sync::Arc},
 };
+pub use {cosmos::*, solana::*};
+
+mod cosmos;
+#[cfg(feature = "eip1271")]
+mod eip1271;
+mod solana;
+
+pub const EIP191: &str = "eip191";
+pub const EIP1271: &str = "eip1271";
 
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Hash)]
 pub struct Signature {
@@ -9,12 +23,129 @@ pub struct Signature {
     pub s: String,
 }
 
+static DEFAULT_REGISTRY: Lazy<SignatureRegistry> = Lazy::new(SignatureRegistry::default);
+
+/// Verifies the Cacao signature of a specific type offline.
+pub trait SignatureVerifier: Send + Sync {
+    fn verify_cacao(&self, cacao: &Cacao) -> Result<bool, CacaoError>;
+}
+
+/// Signature verifiers by the signature type (`s.t`). The default registry
+/// supports EIP-191 (Ethereum), ed25519 (Solana) and ADR-036 (Cosmos).
+#[derive(Clone)]
+pub struct SignatureRegistry {
+    verifiers: HashMap<String, Arc<dyn SignatureVerifier>>,
+}
+
+impl SignatureRegistry {
+    /// Creates a registry without any verifiers.
+    pub fn empty() -> Self {
+        Self {
+            verifiers: HashMap::new(),
+        }
+    }
+
+    /// Registers the verifier for the signature type, replacing the existing
+    /// one.
+    pub fn register(
+        mut self,
+        signature_type: impl Into<String>,
+        verifier: impl SignatureVerifier + 'static,
+    ) -> Self {
+        self.verifiers
+            .insert(signature_type.into(), Arc::new(verifier));
+        self
+    }
+
+    pub fn get(&self, signature_type: &str) -> Option<&dyn SignatureVerifier> {
+        self.verifiers.get(signature_type).map(Arc::as_ref)
+    }
+}
+
+impl Default for SignatureRegistry {
+    fn default() -> Self {
+        Self::empty()
+            .register(EIP191, Eip191)
+            .register(SOLANA_ED25519, Ed25519)
+            .register(COSMOS_ADR036, CosmosAdr036)
+    }
+}
+
+/// Verifiers for the signature types that require access to the blockchain,
+/// and the registry for the rest.
+#[cfg(feature = "eip1271")]
+#[derive(Clone, Default)]
+pub struct Verifiers {
+    eip1271: Option<Arc<dyn Eip1271Verifier>>,
+    registry: SignatureRegistry,
+}
+
+#[cfg(feature = "eip1271")]
+impl Verifiers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the verifier for the smart contract wallet signatures.
+    pub fn with_eip1271(mut self, verifier: impl Eip1271Verifier + 'static) -> Self {
+        self.eip1271 = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Sets the registry used for the signature types that can be verified
+    /// offline.
+    pub fn with_registry(mut self, registry: SignatureRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+}
+
 impl Signature {
+    /// Verifies the signature using the default [`SignatureRegistry`]. Only
+    /// the signature types that can be verified offline are supported. See
+    /// `Signature::verify_with()` (the `eip1271` feature) for the rest.
     pub fn verify(&self, cacao: &Cacao) -> Result<bool, CacaoError> {
+        self.verify_with_registry(cacao, &DEFAULT_REGISTRY)
+    }
+
+    pub fn verify_with_registry(
+        &self,
+        cacao: &Cacao,
+        registry: &SignatureRegistry,
+    ) -> Result<bool, CacaoError> {
+        registry
+            .get(&self.t)
+            .ok_or(CacaoError::UnsupportedSignature)?
+            .verify_cacao(cacao)
+    }
+
+    /// Verifies the signature, using the provided [`Verifiers`] for the
+    /// signature types that require access to the blockchain.
+    #[cfg(feature = "eip1271")]
+    pub async fn verify_with(
+        &self,
+        cacao: &Cacao,
+        verifiers: &Verifiers,
+    ) -> Result<bool, CacaoError> {
         match self.t.as_str() {
-            "eip191" => Eip191.verify(&cacao.s.s, &cacao.p.address()?, &cacao.siwe_message()?),
-            // "eip1271" => Eip1271.verify(), TODO: How to accces our RPC?
-            _ => Err(CacaoError::UnsupportedSignature),
+            EIP1271 => {
+                let verifier = verifiers
+                    .eip1271
+                    .as_deref()
+                    .ok_or(CacaoError::UnsupportedSignature)?;
+
+                Eip1271
+                    .verify(
+                        verifier,
+                        &cacao.s.s,
+                        &cacao.p.chain_id_reference()?,
+                        &cacao.p.address()?,
+                        &cacao.siwe_message()?,
+                    )
+                    .await
+            }
+
+            _ => self.verify_with_registry(cacao, &verifiers.registry),
         }
     }
 }
@@ -31,9 +162,24 @@ impl Eip191 {
         .into()
     }
 
+    /// Signs the message, returning the hex-encoded `r || s || v` signature,
+    /// the inverse of the EIP-191 verification.
+    pub fn sign(&self, key: &SigningKey, message: &str) -> Result<String, CacaoError> {
+        use sha3::{Digest, Keccak256};
+
+        let (signature, recovery_id) = key
+            .sign_digest_recoverable(Keccak256::new_with_prefix(self.eip191_bytes(message)))
+            .map_err(|_| CacaoError::Signing)?;
+
+        let mut bytes = signature.to_bytes().to_vec();
+        bytes.push(recovery_id.to_byte() + 27);
+
+        Ok(format!("0x{}", data_encoding::HEXLOWER.encode(&bytes)))
+    }
+
     fn verify(&self, signature: &str, address: &str, message: &str) -> Result<bool, CacaoError> {
         use {
-            k256::ecdsa::{RecoveryId, Signature as Sig, VerifyingKey},
+            k256::ecdsa::{RecoveryId, Signature as Sig},
             sha3::{Digest, Keccak256},
         };
 
@@ -66,6 +212,80 @@ impl Eip191 {
     }
 }
 
+impl SignatureVerifier for Eip191 {
+    fn verify_cacao(&self, cacao: &Cacao) -> Result<bool, CacaoError> {
+        self.verify(&cacao.s.s, &cacao.p.address()?, &cacao.siwe_message()?)
+    }
+}
+
+#[cfg(feature = "eip1271")]
+pub struct Eip1271;
+
+#[cfg(feature = "eip1271")]
+impl Eip1271 {
+    async fn verify(
+        &self,
+        verifier: &dyn Eip1271Verifier,
+        signature: &str,
+        chain_id: &str,
+        address: &str,
+        message: &str,
+    ) -> Result<bool, CacaoError> {
+        use sha3::{Digest, Keccak256};
+
+        let signature_bytes = data_encoding::HEXLOWER_PERMISSIVE
+            .decode(strip_hex_prefix(signature).as_bytes())
+            .map_err(|_| CacaoError::Verification)?;
+
+        let hash = Keccak256::new_with_prefix(Eip191.eip191_bytes(message)).finalize();
+
+        if verifier
+            .is_valid_signature(chain_id, address, hash.into(), &signature_bytes)
+            .await?
+        {
+            Ok(true)
+        } else {
+            Err(CacaoError::Verification)
+        }
+    }
+}
+
+/// Returns the EIP-55 checksummed Ethereum address of the public key.
+pub fn eip155_address(key: &VerifyingKey) -> String {
+    use sha3::{Digest, Keccak256};
+
+    let hash = Keccak256::default()
+        .chain_update(&key.to_encoded_point(false).as_bytes()[1..])
+        .finalize();
+
+    eip55_checksum(&data_encoding::HEXLOWER.encode(&hash[12..]))
+}
+
+/// Applies the EIP-55 checksum to the hex-encoded Ethereum address, with or
+/// without the "0x" prefix.
+pub fn eip55_checksum(address: &str) -> String {
+    use sha3::{Digest, Keccak256};
+
+    let address = strip_hex_prefix(address).to_ascii_lowercase();
+    let checksum = Keccak256::digest(address.as_bytes());
+
+    let address = address
+        .char_indices()
+        .map(|(idx, ch)| {
+            // Uppercase the letter if the corresponding nibble of the checksum is >= 8.
+            let nibble = (checksum[idx / 2] >> (4 * (1 - idx % 2))) & 0x0f;
+
+            if nibble >= 8 {
+                ch.to_ascii_uppercase()
+            } else {
+                ch
+            }
+        })
+        .collect::<String>();
+
+    format!("0x{address}")
+}
+
 /// Remove the "0x" prefix from a hex string.
 fn strip_hex_prefix(s: &str) -> &str {
     s.strip_prefix("0x").unwrap_or(s)
@@ -0,0 +1,100 @@
+use {
+    super::{Cacao, CacaoError, SignatureVerifier},
+    bech32::{FromBase32, ToBase32, Variant},
+    k256::ecdsa::{RecoveryId, Signature as Sig, SigningKey, VerifyingKey},
+    ripemd::Ripemd160,
+    sha2::{Digest, Sha256},
+};
+
+pub const COSMOS_ADR036: &str = "cosmos:ADR-036";
+
+/// Cosmos ADR-036 signatures of arbitrary data, as produced by e.g. Keplr's
+/// `signArbitrary()`. The signature is the base64-encoded 64-byte `r || s` of
+/// the amino JSON sign doc, with `s` in the lower half of the curve order, and
+/// the address is bech32-encoded.
+pub struct CosmosAdr036;
+
+impl CosmosAdr036 {
+    /// Returns the ADR-036 sign doc for the message signed by the bech32
+    /// address.
+    pub fn sign_doc(&self, signer: &str, message: &str) -> String {
+        // Amino JSON with the keys sorted and no whitespace. The signer is bech32
+        // and the data is base64, so there's nothing to escape.
+        format!(
+            r#"{{"account_number":"0","chain_id":"","fee":{{"amount":[],"gas":"0"}},"memo":"","msgs":[{{"type":"sign/MsgSignData","value":{{"data":"{}","signer":"{}"}}}}],"sequence":"0"}}"#,
+            data_encoding::BASE64.encode(message.as_bytes()),
+            signer
+        )
+    }
+
+    /// Signs the message, returning the base64-encoded signature.
+    pub fn sign(
+        &self,
+        key: &SigningKey,
+        signer: &str,
+        message: &str,
+    ) -> Result<String, CacaoError> {
+        let (signature, _) = key
+            .sign_digest_recoverable(Sha256::new_with_prefix(self.sign_doc(signer, message)))
+            .map_err(|_| CacaoError::Signing)?;
+
+        Ok(data_encoding::BASE64.encode(&signature.to_bytes()))
+    }
+
+    pub fn verify(
+        &self,
+        signature: &str,
+        address: &str,
+        message: &str,
+    ) -> Result<bool, CacaoError> {
+        let signature = data_encoding::BASE64
+            .decode(signature.as_bytes())
+            .ok()
+            .and_then(|signature| Sig::from_slice(&signature).ok())
+            .ok_or(CacaoError::Verification)?;
+
+        // The Cosmos SDK only accepts the low-S form. Reject the malleated
+        // signatures explicitly, instead of relying on the key recovery below.
+        if signature.normalize_s().is_some() {
+            return Err(CacaoError::Verification);
+        }
+
+        let (_, data, _) = bech32::decode(address).map_err(|_| CacaoError::Verification)?;
+        let address_bytes = Vec::<u8>::from_base32(&data).map_err(|_| CacaoError::Verification)?;
+        let hash = Sha256::digest(self.sign_doc(address, message));
+
+        // The signature doesn't carry the recovery ID, so try both and compare
+        // the recovered key with the address.
+        let verified = [0, 1]
+            .into_iter()
+            .filter_map(RecoveryId::from_byte)
+            .filter_map(|recovery_id| {
+                VerifyingKey::recover_from_prehash(&hash, &signature, recovery_id).ok()
+            })
+            .any(|key| account_id(&key) == address_bytes);
+
+        if verified {
+            Ok(true)
+        } else {
+            Err(CacaoError::Verification)
+        }
+    }
+}
+
+impl SignatureVerifier for CosmosAdr036 {
+    fn verify_cacao(&self, cacao: &Cacao) -> Result<bool, CacaoError> {
+        self.verify(&cacao.s.s, &cacao.p.address()?, &cacao.message()?)
+    }
+}
+
+/// Returns the bech32 Cosmos address of the public key with the provided
+/// human-readable prefix, e.g. `cosmos`.
+pub fn cosmos_address(key: &VerifyingKey, prefix: &str) -> Result<String, CacaoError> {
+    bech32::encode(prefix, account_id(key).to_base32(), Variant::Bech32)
+        .map_err(|_| CacaoError::PayloadIssuer)
+}
+
+/// `RIPEMD160(SHA256(compressed public key))`.
+fn account_id(key: &VerifyingKey) -> Vec<u8> {
+    Ripemd160::digest(Sha256::digest(key.to_encoded_point(true).as_bytes())).to_vec()
+}
@@ -0,0 +1,164 @@
+use {
+    crate::auth::cacao::CacaoError,
+    async_trait::async_trait,
+    serde::{Deserialize, Serialize},
+    std::collections::HashMap,
+};
+
+/// Selector of the `isValidSignature(bytes32,bytes)` contract function.
+pub const EIP1271_SELECTOR: [u8; 4] = [0x16, 0x26, 0xba, 0x7e];
+
+/// The value returned by `isValidSignature()` if the signature is valid.
+pub const EIP1271_MAGIC_VALUE: [u8; 4] = EIP1271_SELECTOR;
+
+const ABI_WORD_LENGTH: usize = 32;
+
+/// Performs the EIP-1271 `isValidSignature()` call to a smart contract wallet.
+#[async_trait]
+pub trait Eip1271Verifier: Send + Sync {
+    /// Calls `isValidSignature(hash, signature)` on the contract at `address`
+    /// deployed on the `chain_id` chain (CAIP-2 format, e.g. `eip155:1`).
+    /// Returns `true` if the contract returned [`EIP1271_MAGIC_VALUE`].
+    async fn is_valid_signature(
+        &self,
+        chain_id: &str,
+        address: &str,
+        hash: [u8; 32],
+        signature: &[u8],
+    ) -> Result<bool, CacaoError>;
+}
+
+/// ABI-encodes the `isValidSignature(bytes32,bytes)` call data.
+pub fn encode_is_valid_signature(hash: &[u8; 32], signature: &[u8]) -> Vec<u8> {
+    let padded_len = match signature.len() % ABI_WORD_LENGTH {
+        0 => signature.len(),
+        rem => signature.len() + ABI_WORD_LENGTH - rem,
+    };
+    let total_len = EIP1271_SELECTOR.len() + ABI_WORD_LENGTH * 3 + padded_len;
+
+    let mut data = Vec::with_capacity(total_len);
+    data.extend_from_slice(&EIP1271_SELECTOR);
+    data.extend_from_slice(hash);
+    // Offset of the dynamic `bytes` argument, following the two head words.
+    data.extend_from_slice(&abi_word(ABI_WORD_LENGTH * 2));
+    data.extend_from_slice(&abi_word(signature.len()));
+    data.extend_from_slice(signature);
+    data.resize(total_len, 0);
+    data
+}
+
+fn abi_word(value: usize) -> [u8; ABI_WORD_LENGTH] {
+    let value = (value as u64).to_be_bytes();
+    let mut word = [0u8; ABI_WORD_LENGTH];
+    word[ABI_WORD_LENGTH - value.len()..].copy_from_slice(&value);
+    word
+}
+
+/// [`Eip1271Verifier`] that performs `eth_call` requests to Ethereum JSON-RPC
+/// endpoints.
+#[derive(Debug, Clone, Default)]
+pub struct JsonRpcEip1271Verifier {
+    client: reqwest::Client,
+    endpoints: HashMap<String, String>,
+}
+
+impl JsonRpcEip1271Verifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the provided HTTP client for the RPC requests.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sets the JSON-RPC endpoint URL for the chain (CAIP-2 format, e.g.
+    /// `eip155:1`).
+    pub fn with_endpoint(mut self, chain_id: impl Into<String>, url: impl Into<String>) -> Self {
+        self.endpoints.insert(chain_id.into(), url.into());
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct EthCallRequest<'a> {
+    jsonrpc: &'a str,
+    id: u64,
+    method: &'a str,
+    params: (EthCallParams<'a>, &'a str),
+}
+
+#[derive(Serialize)]
+struct EthCallParams<'a> {
+    to: &'a str,
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct EthCallResponse {
+    result: Option<String>,
+    error: Option<serde_json::Value>,
+}
+
+#[async_trait]
+impl Eip1271Verifier for JsonRpcEip1271Verifier {
+    async fn is_valid_signature(
+        &self,
+        chain_id: &str,
+        address: &str,
+        hash: [u8; 32],
+        signature: &[u8],
+    ) -> Result<bool, CacaoError> {
+        let url = self
+            .endpoints
+            .get(chain_id)
+            .ok_or_else(|| CacaoError::Eip1271(format!("No RPC endpoint for chain {chain_id}")))?;
+
+        let data = encode_is_valid_signature(&hash, signature);
+        let request = EthCallRequest {
+            jsonrpc: crate::rpc::JSON_RPC_VERSION_STR,
+            id: 1,
+            method: "eth_call",
+            params: (
+                EthCallParams {
+                    to: address,
+                    data: format!("0x{}", data_encoding::HEXLOWER.encode(&data)),
+                },
+                "latest",
+            ),
+        };
+
+        let response = self
+            .client
+            .post(url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| CacaoError::Eip1271(err.to_string()))?
+            .json::<EthCallResponse>()
+            .await
+            .map_err(|err| CacaoError::Eip1271(err.to_string()))?;
+
+        let result = match response {
+            EthCallResponse {
+                result: Some(result),
+                ..
+            } => result,
+
+            EthCallResponse { error, .. } => {
+                let error = error.map(|err| err.to_string()).unwrap_or_default();
+                return Err(CacaoError::Eip1271(format!("RPC error: {error}")));
+            }
+        };
+
+        let result = data_encoding::HEXLOWER_PERMISSIVE
+            .decode(super::strip_hex_prefix(&result).as_bytes())
+            .map_err(|_| CacaoError::Eip1271("Invalid RPC result encoding".to_owned()))?;
+
+        // The result is `bytes4` padded to a full ABI word. Contracts not implementing
+        // EIP-1271 may return nothing at all.
+        Ok(result.starts_with(&EIP1271_MAGIC_VALUE))
+    }
+}
@@ -0,0 +1,46 @@
+use {
+    super::{Cacao, CacaoError, SignatureVerifier},
+    ed25519_dalek::{Keypair, PublicKey, Signer, Verifier},
+};
+
+pub const SOLANA_ED25519: &str = "solana:ed25519";
+
+/// ed25519 signatures of the CAIP-122 message, as produced by Solana wallets.
+/// Both the address and the signature are base58-encoded.
+pub struct Ed25519;
+
+impl Ed25519 {
+    /// Signs the message, returning the base58-encoded signature.
+    pub fn sign(&self, keypair: &Keypair, message: &str) -> String {
+        bs58::encode(keypair.sign(message.as_bytes()).to_bytes()).into_string()
+    }
+
+    pub fn verify(
+        &self,
+        signature: &str,
+        address: &str,
+        message: &str,
+    ) -> Result<bool, CacaoError> {
+        let key = bs58::decode(address)
+            .into_vec()
+            .ok()
+            .and_then(|key| PublicKey::from_bytes(&key).ok())
+            .ok_or(CacaoError::Verification)?;
+
+        let signature = bs58::decode(signature)
+            .into_vec()
+            .ok()
+            .and_then(|signature| ed25519_dalek::Signature::try_from(signature.as_slice()).ok())
+            .ok_or(CacaoError::Verification)?;
+
+        key.verify(message.as_bytes(), &signature)
+            .map(|_| true)
+            .map_err(|_| CacaoError::Verification)
+    }
+}
+
+impl SignatureVerifier for Ed25519 {
+    fn verify_cacao(&self, cacao: &Cacao) -> Result<bool, CacaoError> {
+        self.verify(&cacao.s.s, &cacao.p.address()?, &cacao.message()?)
+    }
+}
@@ -1,4 +1,38 @@
-use crate::auth::cacao::Cacao;
+#[cfg(feature = "eip1271")]
+use {
+    crate::auth::cacao::signature::{encode_is_valid_signature, JsonRpcEip1271Verifier, Verifiers},
+    sha3::{Digest, Keccak256},
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::mpsc,
+    },
+};
+use {
+    crate::auth::cacao::{
+        builder::CacaoBuilder,
+        header::{Header, CAIP122},
+        message::MessageError,
+        payload::{Payload, ValidationOptions},
+        recap::{ReCap, ReCapError},
+        signature::{
+            cosmos_address,
+            eip155_address,
+            CosmosAdr036,
+            Ed25519,
+            Eip191,
+            Signature,
+            SignatureRegistry,
+            COSMOS_ADR036,
+            SOLANA_ED25519,
+        },
+        Cacao,
+        CacaoError,
+    },
+    chrono::{DateTime, Utc},
+    ed25519_dalek::Keypair,
+    k256::ecdsa::{Signature as Sig, SigningKey},
+};
 
 /// Test that we can verify a deprecated Cacao.
 #[test]
@@ -100,3 +134,765 @@ fn cacao_verify_failure() {
     let result = cacao.verify();
     assert!(result.is_err());
 }
+
+/// Starts a mock Ethereum JSON-RPC server that responds to every request with
+/// the provided `eth_call` result. Returns the server URL and a receiver for
+/// the `eth_call` data of the received requests.
+#[cfg(feature = "eip1271")]
+async fn mock_eth_rpc(result: &'static str) -> (String, mpsc::UnboundedReceiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut buf = Vec::new();
+
+            // Read until the whole body (as specified by `content-length`) is received.
+            let body = loop {
+                let mut chunk = [0u8; 1024];
+                let len = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..len]);
+
+                let request = String::from_utf8_lossy(&buf).into_owned();
+
+                if let Some((headers, body)) = request.split_once("\r\n\r\n") {
+                    let content_length = headers
+                        .lines()
+                        .find_map(|line| {
+                            let (name, value) = line.split_once(':')?;
+                            name.eq_ignore_ascii_case("content-length")
+                                .then(|| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+
+                    if body.len() >= content_length {
+                        break body.to_owned();
+                    }
+                }
+            };
+
+            let request: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert_eq!(request["method"], "eth_call");
+            tx.send(request["params"][0]["data"].as_str().unwrap().to_owned())
+                .ok();
+
+            let response = format!(r#"{{"jsonrpc":"2.0","id":1,"result":"{result}"}}"#);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: \
+                 {}\r\nconnection: close\r\n\r\n{response}",
+                response.len()
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    (url, rx)
+}
+
+fn eip1271_cacao() -> Cacao {
+    let cacao_serialized = r#"{
+      "h": {
+        "t": "eip4361"
+      },
+      "p": {
+        "iss": "did:pkh:eip155:1:0x2faf83c542b68f1b4cdc0e770e8cb9f567b08f71",
+        "domain": "app.example.com",
+        "aud": "https://app.example.com/login",
+        "version": "1",
+        "nonce": "9a4b8e6ad1b2c0d74c3b7e0e",
+        "iat": "2023-09-12T10:24:31Z"
+      },
+      "s": {
+        "t": "eip1271",
+        "s": "0xdeadbeef"
+      }
+    }"#;
+
+    serde_json::from_str(cacao_serialized).unwrap()
+}
+
+#[cfg(feature = "eip1271")]
+#[test]
+fn eip1271_calldata() {
+    let data = encode_is_valid_signature(&[0x11; 32], &[0xde, 0xad, 0xbe, 0xef]);
+
+    assert_eq!(
+        data_encoding::HEXLOWER.encode(&data),
+        concat!(
+            "1626ba7e",
+            "1111111111111111111111111111111111111111111111111111111111111111",
+            "0000000000000000000000000000000000000000000000000000000000000040",
+            "0000000000000000000000000000000000000000000000000000000000000004",
+            "deadbeef00000000000000000000000000000000000000000000000000000000",
+        )
+    );
+}
+
+/// Test that we can verify a smart contract wallet Cacao.
+#[cfg(feature = "eip1271")]
+#[tokio::test]
+async fn cacao_verify_eip1271_success() {
+    let cacao = eip1271_cacao();
+
+    // Offline verification is not possible.
+    assert!(matches!(
+        cacao.verify(),
+        Err(CacaoError::UnsupportedSignature)
+    ));
+    assert!(matches!(
+        cacao.verify_with(&Verifiers::new()).await,
+        Err(CacaoError::UnsupportedSignature)
+    ));
+
+    let (url, mut requests) =
+        mock_eth_rpc("0x1626ba7e00000000000000000000000000000000000000000000000000000000").await;
+    let verifiers =
+        Verifiers::new().with_eip1271(JsonRpcEip1271Verifier::new().with_endpoint("eip155:1", url));
+
+    assert!(cacao.verify_with(&verifiers).await.unwrap());
+
+    let data = requests.recv().await.unwrap();
+    let hash =
+        Keccak256::new_with_prefix(Eip191.eip191_bytes(&cacao.siwe_message().unwrap())).finalize();
+    let expected = encode_is_valid_signature(&hash.into(), &[0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(
+        data,
+        format!("0x{}", data_encoding::HEXLOWER.encode(&expected))
+    );
+}
+
+/// Test that a smart contract wallet Cacao is rejected if the contract doesn't
+/// return the magic value.
+#[cfg(feature = "eip1271")]
+#[tokio::test]
+async fn cacao_verify_eip1271_failure() {
+    let cacao = eip1271_cacao();
+
+    let (url, _requests) =
+        mock_eth_rpc("0xffffffff00000000000000000000000000000000000000000000000000000000").await;
+    let verifiers = Verifiers::new()
+        .with_eip1271(JsonRpcEip1271Verifier::new().with_endpoint("eip155:1", url.clone()));
+
+    assert!(matches!(
+        cacao.verify_with(&verifiers).await,
+        Err(CacaoError::Verification)
+    ));
+
+    // No endpoint for the chain.
+    let verifiers = Verifiers::new()
+        .with_eip1271(JsonRpcEip1271Verifier::new().with_endpoint("eip155:10", url));
+
+    assert!(matches!(
+        cacao.verify_with(&verifiers).await,
+        Err(CacaoError::Eip1271(_))
+    ));
+}
+
+/// Test the payload validation rules.
+#[test]
+fn payload_validation() {
+    let payload = eip1271_cacao().p;
+    let iat = DateTime::parse_from_rfc3339(&payload.iat)
+        .unwrap()
+        .with_timezone(&Utc);
+    let options = ValidationOptions::default().with_clock(move || iat);
+
+    assert!(payload.validate_with(&options).is_ok());
+
+    let check = |update: fn(&mut Payload)| {
+        let mut payload = payload.clone();
+        update(&mut payload);
+        payload.validate_with(&options)
+    };
+
+    // Domain and audience.
+    assert!(
+        check(|p| p.aud = "did:key:z6MkvjNoiz9AXGH1igzrtB54US5hE9bZPQm1ryKGkCLwWht7".into())
+            .is_ok()
+    );
+    assert!(check(|p| p.domain = "app.example.com:8080".into()).is_err());
+    assert!(check(|p| {
+        p.domain = "app.example.com:8080".into();
+        p.aud = "https://user@APP.example.com:8080/login".into();
+    })
+    .is_ok());
+    assert!(matches!(
+        check(|p| p.domain = "https://app.example.com".into()),
+        Err(CacaoError::PayloadDomain)
+    ));
+    assert!(matches!(
+        check(|p| p.aud = "app.example.com/login".into()),
+        Err(CacaoError::PayloadAudience)
+    ));
+    assert!(matches!(
+        check(|p| p.aud = "https://evil.example.com/login".into()),
+        Err(CacaoError::PayloadDomainMismatch)
+    ));
+
+    // Nonce.
+    assert!(matches!(
+        check(|p| p.nonce = " ".into()),
+        Err(CacaoError::PayloadNonce)
+    ));
+
+    // Issuer.
+    assert!(matches!(
+        check(|p| p.iss = "did:key:z6MkvjNoiz9AXGH1igzrtB54US5hE9bZPQm1ryKGkCLwWht7".into()),
+        Err(CacaoError::PayloadIssuer)
+    ));
+    assert!(matches!(
+        check(|p| p.iss = "did:pkh:eip155:0x2faf83c542b68f1b4cdc0e770e8cb9f567b08f71".into()),
+        Err(CacaoError::PayloadIssuer)
+    ));
+
+    // Timestamps.
+    assert!(matches!(
+        check(|p| p.iat = "2023-09-12 10:24:31".into()),
+        Err(CacaoError::PayloadTimestamp("iat"))
+    ));
+    assert!(matches!(
+        check(|p| p.exp = Some("tomorrow".into())),
+        Err(CacaoError::PayloadTimestamp("exp"))
+    ));
+    assert!(check(|p| p.iat = "2023-09-12T10:26:31Z".into()).is_ok());
+    assert!(matches!(
+        check(|p| p.iat = "2023-09-12T10:26:32Z".into()),
+        Err(CacaoError::PayloadIssuedInFuture)
+    ));
+    assert!(check(|p| p.exp = Some("2023-09-12T10:22:31Z".into())).is_ok());
+    assert!(matches!(
+        check(|p| p.exp = Some("2023-09-12T10:22:30Z".into())),
+        Err(CacaoError::PayloadExpired)
+    ));
+    assert!(check(|p| p.nbf = Some("2023-09-12T12:26:31+02:00".into())).is_ok());
+    assert!(matches!(
+        check(|p| p.nbf = Some("2023-09-12T12:26:32+02:00".into())),
+        Err(CacaoError::PayloadNotYetValid)
+    ));
+    assert!(matches!(
+        payload.validate_with(
+            &options
+                .clone()
+                .with_time_leeway(0)
+                .with_clock(move || { iat - chrono::Duration::seconds(1) })
+        ),
+        Err(CacaoError::PayloadIssuedInFuture)
+    ));
+
+    // Resources.
+    assert!(check(|p| {
+        p.resources = Some(vec![
+            "ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/".into(),
+            "https://example.com/my-web2-claim.json".into(),
+            "identity.walletconnect.com".into(),
+        ])
+    })
+    .is_ok());
+    assert!(matches!(
+        check(|p| p.resources = Some(vec!["not a uri".into()])),
+        Err(CacaoError::PayloadResourceUri(_))
+    ));
+}
+
+fn random_signing_key() -> SigningKey {
+    // `k256` uses a newer `rand_core` than the one provided by `rand` 0.7.
+    SigningKey::from_slice(&rand::random::<[u8; 32]>()).unwrap()
+}
+
+/// Test that a Cacao created by the builder can be verified.
+#[test]
+fn cacao_builder_sign() {
+    let key = random_signing_key();
+    let iat = Utc::now();
+
+    let cacao = CacaoBuilder::new("app.example.com", "https://app.example.com/login")
+        .chain_id("137")
+        .statement("I accept the Terms of Service".to_owned())
+        .iat(iat)
+        .exp(iat + chrono::Duration::hours(1))
+        .request_id("req-1".to_owned())
+        .resource("ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/")
+        .sign(&key)
+        .unwrap();
+
+    assert_eq!(cacao.h.t, "eip4361");
+    assert_eq!(cacao.s.t, "eip191");
+    assert_eq!(cacao.p.chain_id().unwrap(), "137");
+    assert_eq!(
+        cacao.p.address().unwrap(),
+        eip155_address(key.verifying_key())
+    );
+    assert!(!cacao.p.nonce.is_empty());
+    assert!(cacao.verify().unwrap());
+
+    // Survives serialization.
+    let serialized = serde_json::to_string(&cacao).unwrap();
+    let deserialized: Cacao = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized, cacao);
+    assert!(deserialized.verify().unwrap());
+
+    // Tampered payload.
+    let mut tampered = cacao.clone();
+    tampered.p.statement = Some("I accept nothing".to_owned());
+    assert!(matches!(tampered.verify(), Err(CacaoError::Verification)));
+
+    // Signed by another key.
+    let mut forged = cacao;
+    forged.s.s = Eip191
+        .sign(&random_signing_key(), &forged.siwe_message().unwrap())
+        .unwrap();
+    assert!(matches!(forged.verify(), Err(CacaoError::Verification)));
+}
+
+/// Test the EIP-55 address checksum.
+#[test]
+fn eip55_checksum() {
+    // Private key `1`, a well-known test vector.
+    let mut secret = [0u8; 32];
+    secret[31] = 1;
+    let key = SigningKey::from_slice(&secret).unwrap();
+
+    assert_eq!(
+        eip155_address(key.verifying_key()),
+        "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+    );
+}
+
+/// Test that the rendered SIWE message can be parsed back into the payload.
+#[test]
+fn siwe_message_round_trip() {
+    let iat = Utc::now();
+    let builders = [
+        CacaoBuilder::new("app.example.com", "https://app.example.com/login"),
+        CacaoBuilder::new("localhost:3000", "http://localhost:3000/login")
+            .chain_id("137")
+            .statement("I accept the Terms of Service: https://app.example.com/tos".to_owned())
+            .iat(iat)
+            .exp(iat + chrono::Duration::hours(1))
+            .nbf(iat)
+            .request_id("req-1".to_owned())
+            .resource("ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/")
+            .resource("https://example.com/my-web2-claim.json"),
+    ];
+
+    for builder in builders {
+        let cacao = builder.sign(&random_signing_key()).unwrap();
+        let message = cacao.siwe_message().unwrap();
+
+        let payload = Payload::from_siwe_message(&message).unwrap();
+        assert_eq!(payload, cacao.p);
+        assert_eq!(Payload::from_caip122_message(&message).unwrap(), cacao.p);
+
+        let parsed = Cacao {
+            p: payload,
+            ..cacao
+        };
+        assert_eq!(parsed.siwe_message().unwrap(), message);
+        assert!(parsed.verify().unwrap());
+    }
+
+    // A Cacao that doesn't meet the strict EIP-4361 rules can still be parsed as
+    // a generic CAIP-122 message.
+    let cacao = eip1271_cacao();
+    let message = cacao.siwe_message().unwrap();
+    let payload = Payload::from_caip122_message(&message).unwrap();
+    assert_eq!(payload, cacao.p);
+    assert_eq!(
+        Cacao {
+            p: payload,
+            ..cacao
+        }
+        .siwe_message()
+        .unwrap(),
+        message
+    );
+
+    // The empty resources are rendered without the header, and the header
+    // without any resources is rejected.
+    let mut cacao = eip1271_cacao();
+    cacao.p.resources = Some(Vec::new());
+    let message = cacao.siwe_message().unwrap();
+    assert!(!message.contains("Resources:"));
+    let payload = Payload::from_caip122_message(&message).unwrap();
+    assert_eq!(
+        Cacao {
+            p: payload,
+            ..cacao
+        }
+        .siwe_message()
+        .unwrap(),
+        message
+    );
+
+    let lines = message.lines().count();
+    assert_eq!(
+        Payload::from_caip122_message(&format!("{message}\nResources:")),
+        Err(MessageError::InvalidValue {
+            line: lines + 1,
+            field: "Resources:",
+        })
+    );
+}
+
+/// Test that malformed messages are rejected with the offending line.
+#[test]
+fn siwe_message_parse_errors() {
+    let valid = [
+        "service.org wants you to sign in with your Ethereum account:",
+        "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf",
+        "",
+        "I accept the ServiceOrg Terms of Service: https://service.org/tos",
+        "",
+        "URI: https://service.org/login",
+        "Version: 1",
+        "Chain ID: 1",
+        "Nonce: 32891756",
+        "Issued At: 2021-09-30T16:25:24Z",
+        "Resources:",
+        "- ipfs://bafybeiemxf5abjwjbikoz4mc3a3dla6ual3jsgpdr4cjr3oz3evfyavhwq/",
+        "- https://example.com/my-web2-claim.json",
+    ]
+    .join("\n");
+
+    let payload = Payload::from_siwe_message(&valid).unwrap();
+    assert_eq!(
+        payload.iss,
+        "did:pkh:eip155:1:0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf"
+    );
+    assert_eq!(payload.nonce, "32891756");
+    assert_eq!(payload.resources.as_ref().map(Vec::len), Some(2));
+
+    let cases = [
+        (
+            valid.replace("wants you to sign in", "wants to sign in"),
+            MessageError::Preamble(1),
+        ),
+        (
+            valid.replace("Ethereum", "Bitcoin"),
+            MessageError::UnsupportedChain {
+                line: 1,
+                chain: "Bitcoin".to_owned(),
+            },
+        ),
+        (valid.replace("0x7E5F", "0x7e5f"), MessageError::Address(2)),
+        (
+            valid.replace("tos\n\n", "tos\n"),
+            MessageError::ExpectedEmptyLine(5),
+        ),
+        (
+            valid.replace("Version: 1", "Version: 2"),
+            MessageError::InvalidValue {
+                line: 7,
+                field: "Version",
+            },
+        ),
+        (
+            valid.replace("Chain ID", "Chain"),
+            MessageError::MissingField {
+                line: 8,
+                field: "Chain ID",
+            },
+        ),
+        (
+            valid.replace("Nonce: 32891756", "Nonce: 123"),
+            MessageError::InvalidValue {
+                line: 9,
+                field: "Nonce",
+            },
+        ),
+        (
+            valid.replace("2021-09-30T16:25:24Z", "yesterday"),
+            MessageError::InvalidValue {
+                line: 10,
+                field: "Issued At",
+            },
+        ),
+        (
+            valid.replace("- https", "- not a uri"),
+            MessageError::InvalidValue {
+                line: 13,
+                field: "Resources:",
+            },
+        ),
+        (format!("{valid}\n"), MessageError::UnexpectedLine(14)),
+        (
+            valid.split("\nIssued At").next().unwrap().to_owned(),
+            MessageError::UnexpectedEnd("Issued At"),
+        ),
+    ];
+
+    for (message, error) in cases {
+        assert_eq!(Payload::from_siwe_message(&message), Err(error));
+    }
+
+    // The generic CAIP-122 parser doesn't check the values, except for the
+    // CAIP-10 account.
+    let lenient = valid
+        .replace("0x7E5F", "0x7e5f")
+        .replace("Nonce: 32891756", "Nonce: 123");
+    assert!(Payload::from_siwe_message(&lenient).is_err());
+    assert!(Payload::from_caip122_message(&lenient).is_ok());
+    assert_eq!(
+        Payload::from_caip122_message(&valid.replace("0x7E5F", "0x7E5F/")),
+        Err(MessageError::Address(2))
+    );
+}
+
+/// Test the ReCap (EIP-5573) encoding and the generated statement, using the
+/// example from the EIP.
+#[test]
+fn recap_encoding() {
+    let recap: ReCap = serde_json::from_str(
+        r#"{
+          "att": {
+            "https://example.com/pictures/": {
+              "crud/delete": [{}],
+              "crud/update": [{}],
+              "other/action": [{}]
+            },
+            "mailto:username@example.com": {
+              "msg/receive": [{ "max_count": 5, "templates": ["newsletter", "marketing"] }],
+              "msg/send": [{ "to": "someone@email.com" }, { "to": "joe@email.com" }]
+            }
+          },
+          "prf": ["bafybeigk7ly3pog6uupxku3b6bubirr434ib6tfaymvox6gotaaaaaaaaa"]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        recap.statement(),
+        "I further authorize the stated URI to perform the following actions on my behalf: (1) \
+         'crud': 'delete', 'update' for 'https://example.com/pictures/'. (2) 'other': 'action' \
+         for 'https://example.com/pictures/'. (3) 'msg': 'receive', 'send' for \
+         'mailto:username@example.com'."
+    );
+
+    let uri = recap.encode().unwrap();
+    assert!(uri.starts_with("urn:recap:ey"));
+    assert!(!uri.ends_with('='));
+    assert_eq!(ReCap::decode(&uri).unwrap(), recap);
+
+    assert!(recap.allows("mailto:username@example.com", "msg/send"));
+    assert!(!recap.allows("mailto:username@example.com", "crud/delete"));
+    assert_eq!(
+        recap
+            .notes("mailto:username@example.com", "msg/send")
+            .map(<[_]>::len),
+        Some(2)
+    );
+
+    assert_eq!(ReCap::decode("urn:other:e30"), Err(ReCapError::Prefix));
+    assert_eq!(ReCap::decode("urn:recap:e30!"), Err(ReCapError::Encoding));
+    assert!(matches!(
+        ReCap::decode("urn:recap:bm90IGpzb24"),
+        Err(ReCapError::Json(_))
+    ));
+
+    let invalid = ReCap::new().ability("https://example.com", "no-namespace");
+    assert_eq!(
+        invalid.encode(),
+        Err(ReCapError::Ability("no-namespace".to_owned()))
+    );
+}
+
+/// Test that multiple ReCaps are merged.
+#[test]
+fn recap_merge() {
+    let mut recap = ReCap::new()
+        .ability(
+            "https://notify.walletconnect.com",
+            "manage/all-apps-notifications",
+        )
+        .ability("https://example.com", "crud/read");
+    recap.merge(
+        ReCap::new()
+            .ability("https://example.com", "crud/read")
+            .ability("https://example.com", "crud/update"),
+    );
+
+    assert_eq!(recap.att.len(), 2);
+    assert_eq!(
+        recap
+            .notes("https://example.com", "crud/read")
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(recap.allows("https://example.com", "crud/update"));
+    assert!(recap.allows(
+        "https://notify.walletconnect.com",
+        "manage/all-apps-notifications"
+    ));
+}
+
+/// Test that the ReCaps in a Cacao are validated and can be queried.
+#[test]
+fn cacao_recap() {
+    let key = random_signing_key();
+    let notify = ReCap::new().ability(
+        "https://notify.walletconnect.com",
+        "manage/all-apps-notifications",
+    );
+    let identity = ReCap::new().ability("https://keys.walletconnect.com", "identity/register");
+
+    let cacao = CacaoBuilder::new("app.example.com", "https://app.example.com/login")
+        .statement("Sign in to Example.".to_owned())
+        .resource("did:key:z6MkhoV7JnKEFgwai4R1ui14xcPDnqVFZ3a9dUNM3fE3z3Nf")
+        .recap(notify.clone())
+        .recap(identity.clone())
+        .sign(&key)
+        .unwrap();
+
+    assert!(cacao.verify().unwrap());
+    assert_eq!(
+        cacao.p.identity_key().unwrap(),
+        "z6MkhoV7JnKEFgwai4R1ui14xcPDnqVFZ3a9dUNM3fE3z3Nf"
+    );
+    assert!(cacao
+        .p
+        .statement
+        .as_deref()
+        .unwrap()
+        .starts_with("Sign in to Example. I further authorize"));
+
+    let capabilities = cacao.capabilities();
+    assert!(capabilities.allows(
+        "https://notify.walletconnect.com",
+        "manage/all-apps-notifications"
+    ));
+    assert!(capabilities.allows("https://keys.walletconnect.com", "identity/register"));
+    assert!(!capabilities.allows("https://notify.walletconnect.com", "identity/register"));
+
+    // Separately encoded ReCaps are merged.
+    let mut payload = cacao.p.clone();
+    payload.resources = Some(vec![notify.encode().unwrap(), identity.encode().unwrap()]);
+    assert!(payload.validate().is_ok());
+
+    // ReCaps must come last.
+    payload
+        .resources
+        .as_mut()
+        .unwrap()
+        .push("https://example.com".to_owned());
+    assert!(matches!(
+        payload.validate(),
+        Err(CacaoError::PayloadReCapPosition)
+    ));
+
+    // The statement must match the ReCaps.
+    let mut payload = cacao.p.clone();
+    payload.statement = Some("Sign in to Example.".to_owned());
+    assert!(matches!(
+        payload.validate(),
+        Err(CacaoError::PayloadReCapStatement)
+    ));
+
+    // Invalid ReCaps are rejected.
+    let mut payload = cacao.p;
+    payload.resources = Some(vec!["urn:recap:e30!".to_owned()]);
+    assert!(matches!(
+        payload.validate(),
+        Err(CacaoError::PayloadReCap(ReCapError::Encoding))
+    ));
+}
+
+/// Creates an unsigned CAIP-122 Cacao for the `did:pkh` account.
+fn caip122_cacao(account: &str, signature_type: &str) -> Cacao {
+    let mut p = CacaoBuilder::new("app.example.com", "https://app.example.com/login")
+        .statement("Sign in to Example.".to_owned())
+        .build_payload("")
+        .unwrap();
+    p.iss = format!("did:pkh:{account}");
+
+    Cacao {
+        h: Header {
+            t: CAIP122.to_owned(),
+        },
+        p,
+        s: Signature {
+            t: signature_type.to_owned(),
+            s: String::new(),
+        },
+    }
+}
+
+/// Test the Solana ed25519 signatures.
+#[test]
+fn cacao_verify_solana() {
+    let keypair = Keypair::from_bytes(&[
+        215, 142, 127, 216, 153, 183, 205, 110, 103, 118, 181, 195, 60, 71, 5, 221, 100, 196, 207,
+        81, 229, 11, 116, 121, 235, 104, 1, 121, 25, 18, 218, 83, 216, 230, 100, 248, 132, 110, 55,
+        65, 221, 87, 66, 160, 36, 95, 116, 86, 169, 49, 107, 17, 13, 50, 22, 147, 199, 109, 125,
+        155, 89, 190, 186, 171,
+    ])
+    .unwrap();
+    let address = bs58::encode(keypair.public_key().to_bytes()).into_string();
+
+    let mut cacao = caip122_cacao(
+        &format!("solana:5eykt4UsFv8P8NJdTREpY1vzqKqZKvdp:{address}"),
+        SOLANA_ED25519,
+    );
+
+    let message = cacao.message().unwrap();
+    assert!(message.starts_with("app.example.com wants you to sign in with your Solana account:"));
+
+    cacao.s.s = Ed25519.sign(&keypair, &message);
+    assert!(cacao.verify().unwrap());
+
+    // The message can be parsed back.
+    assert_eq!(Payload::from_caip122_message(&message).unwrap(), cacao.p);
+
+    // Tampered payload.
+    let mut tampered = cacao.clone();
+    tampered.p.nonce = "tampered".to_owned();
+    assert!(matches!(tampered.verify(), Err(CacaoError::Verification)));
+
+    // Not supported by an empty registry.
+    assert!(matches!(
+        cacao
+            .s
+            .verify_with_registry(&cacao, &SignatureRegistry::empty()),
+        Err(CacaoError::UnsupportedSignature)
+    ));
+}
+
+/// Test the Cosmos ADR-036 signatures.
+#[test]
+fn cacao_verify_cosmos() {
+    let key = random_signing_key();
+    let address = cosmos_address(key.verifying_key(), "cosmos").unwrap();
+    assert!(address.starts_with("cosmos1"));
+
+    let mut cacao = caip122_cacao(&format!("cosmos:cosmoshub-4:{address}"), COSMOS_ADR036);
+
+    let message = cacao.message().unwrap();
+    assert!(message.starts_with("app.example.com wants you to sign in with your Cosmos account:"));
+
+    cacao.s.s = CosmosAdr036.sign(&key, &address, &message).unwrap();
+    assert!(cacao.verify().unwrap());
+    assert_eq!(Payload::from_caip122_message(&message).unwrap(), cacao.p);
+
+    // The same signature in the malleated high-S form.
+    let signature = data_encoding::BASE64.decode(cacao.s.s.as_bytes()).unwrap();
+    let (r, s) = Sig::from_slice(&signature).unwrap().split_scalars();
+    let mut malleated = cacao.clone();
+    malleated.s.s = data_encoding::BASE64.encode(&Sig::from_scalars(r, -s).unwrap().to_bytes());
+    assert!(matches!(malleated.verify(), Err(CacaoError::Verification)));
+
+    // Signed by another key.
+    let mut forged = cacao.clone();
+    forged.s.s = CosmosAdr036
+        .sign(&random_signing_key(), &address, &message)
+        .unwrap();
+    assert!(matches!(forged.verify(), Err(CacaoError::Verification)));
+
+    // Unknown namespace.
+    let mut unknown = cacao;
+    unknown.p.iss = format!("did:pkh:unknown:1:{address}");
+    assert!(matches!(
+        unknown.verify(),
+        Err(CacaoError::UnsupportedNamespace(_))
+    ));
+}
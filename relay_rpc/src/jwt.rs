@@ -1,6 +1,7 @@
 use {
     crate::domain::DidKey,
     chrono::Utc,
+    curve25519_dalek::edwards::CompressedEdwardsY,
     ed25519_dalek::{ed25519::signature::Signature, Keypair, PublicKey, Signer},
     serde::{de::DeserializeOwned, Deserialize, Serialize},
     std::collections::HashSet,
@@ -73,6 +74,120 @@ impl<'a> JwtHeader<'a> {
     }
 }
 
+/// Decoded JWT with the signature not yet verified.
+struct UnverifiedJwt<'a, T> {
+    claims: T,
+    message: &'a str,
+    signature: &'a str,
+}
+
+impl<'a, T> UnverifiedJwt<'a, T>
+where
+    T: VerifyableClaims,
+{
+    /// Decodes the header and the claims, and splits out the signed message
+    /// and the signature.
+    fn decode(data: &'a str) -> Result<Self, JwtError> {
+        let mut parts = data.splitn(3, JWT_DELIMITER);
+
+        let (Some(header), Some(claims)) = (parts.next(), parts.next()) else {
+            return Err(JwtError::Format);
+        };
+
+        let decoder = &data_encoding::BASE64URL_NOPAD;
+
+        let header_len = decoder
+            .decode_len(header.len())
+            .map_err(|_| JwtError::Encoding)?;
+        let claims_len = decoder
+            .decode_len(claims.len())
+            .map_err(|_| JwtError::Encoding)?;
+
+        let mut output = vec![0u8; header_len.max(claims_len)];
+
+        // Decode header.
+        data_encoding::BASE64URL_NOPAD
+            .decode_mut(header.as_bytes(), &mut output[..header_len])
+            .map_err(|_| JwtError::Encoding)?;
+
+        {
+            let header = serde_json::from_slice::<JwtHeader>(&output[..header_len])
+                .map_err(JwtError::Serialization)?;
+
+            if !header.is_valid() {
+                return Err(JwtError::Header);
+            }
+        }
+
+        // Decode claims.
+        data_encoding::BASE64URL_NOPAD
+            .decode_mut(claims.as_bytes(), &mut output[..claims_len])
+            .map_err(|_| JwtError::Encoding)?;
+
+        let claims =
+            serde_json::from_slice::<T>(&output[..claims_len]).map_err(JwtError::Serialization)?;
+
+        let mut parts = data.rsplitn(2, JWT_DELIMITER);
+
+        let (Some(signature), Some(message)) = (parts.next(), parts.next()) else {
+            return Err(JwtError::Format);
+        };
+
+        Ok(Self {
+            claims,
+            message,
+            signature,
+        })
+    }
+
+    /// Decodes the issuer public key and the signature for use with
+    /// `ed25519_dalek` verification.
+    fn signature_parts(&self) -> Result<(PublicKey, ed25519_dalek::Signature), JwtError> {
+        let key = PublicKey::from_bytes(self.claims.basic().iss.as_ref())
+            .map_err(|_| JwtError::Signature)?;
+
+        let decoder = &data_encoding::BASE64URL_NOPAD;
+
+        let signature_len = decoder
+            .decode_len(self.signature.len())
+            .map_err(|_| JwtError::Signature)?;
+
+        if signature_len != ed25519_dalek::SIGNATURE_LENGTH {
+            return Err(JwtError::Signature);
+        }
+
+        let mut signature = [0u8; ed25519_dalek::SIGNATURE_LENGTH];
+
+        decoder
+            .decode_mut(self.signature.as_bytes(), &mut signature)
+            .map_err(|_| JwtError::Signature)?;
+
+        // Only accept the key and the signature `R` from the prime-order subgroup.
+        // For those, the cofactored batch verification and the cofactorless
+        // single one agree, so a token is accepted the same way regardless of
+        // the other tokens in the batch.
+        let mut r = [0u8; 32];
+        r.copy_from_slice(&signature[..32]);
+
+        if !is_prime_order(key.to_bytes()) || !is_prime_order(r) {
+            return Err(JwtError::Signature);
+        }
+
+        let signature =
+            ed25519_dalek::Signature::from_bytes(&signature).map_err(|_| JwtError::Signature)?;
+
+        Ok((key, signature))
+    }
+}
+
+/// Returns whether the encoded curve point is in the prime-order subgroup,
+/// i.e. it has no small-order component.
+fn is_prime_order(point: [u8; 32]) -> bool {
+    CompressedEdwardsY(point)
+        .decompress()
+        .is_some_and(|point| !point.is_small_order() && point.is_torsion_free())
+}
+
 /// Basic JWT claims that are common to all JWTs used by the Relay.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct JwtBasicClaims {
@@ -131,50 +246,16 @@ pub trait VerifyableClaims: Serialize + DeserializeOwned {
     where
         Self: Sized,
     {
-        let mut parts = data.splitn(3, JWT_DELIMITER);
-
-        let (Some(header), Some(claims)) = (parts.next(), parts.next()) else {
-            return Err(JwtError::Format);
-        };
-
-        let decoder = &data_encoding::BASE64URL_NOPAD;
-
-        let header_len = decoder
-            .decode_len(header.len())
-            .map_err(|_| JwtError::Encoding)?;
-        let claims_len = decoder
-            .decode_len(claims.len())
-            .map_err(|_| JwtError::Encoding)?;
+        let jwt = UnverifiedJwt::<Self>::decode(data)?;
 
-        let mut output = vec![0u8; header_len.max(claims_len)];
+        // Same key and signature checks as in `verify_many()`.
+        jwt.signature_parts()?;
 
-        // Decode header.
-        data_encoding::BASE64URL_NOPAD
-            .decode_mut(header.as_bytes(), &mut output[..header_len])
-            .map_err(|_| JwtError::Encoding)?;
-
-        {
-            let header = serde_json::from_slice::<JwtHeader>(&output[..header_len])
-                .map_err(JwtError::Serialization)?;
-
-            if !header.is_valid() {
-                return Err(JwtError::Header);
-            }
-        }
-
-        // Decode claims.
-        data_encoding::BASE64URL_NOPAD
-            .decode_mut(claims.as_bytes(), &mut output[..claims_len])
-            .map_err(|_| JwtError::Encoding)?;
-
-        let claims = serde_json::from_slice::<Self>(&output[..claims_len])
-            .map_err(JwtError::Serialization)?;
-
-        let mut parts = data.rsplitn(2, JWT_DELIMITER);
-
-        let (Some(signature), Some(message)) = (parts.next(), parts.next()) else {
-            return Err(JwtError::Format);
-        };
+        let UnverifiedJwt {
+            claims,
+            message,
+            signature,
+        } = jwt;
 
         let key = jsonwebtoken::DecodingKey::from_ed_der(claims.basic().iss.as_ref());
 
@@ -193,6 +274,64 @@ pub trait VerifyableClaims: Serialize + DeserializeOwned {
         }
     }
 
+    /// Same as [`VerifyableClaims::try_from_str()`], but parses multiple tokens
+    /// at once and verifies their signatures using ed25519 batch verification,
+    /// which is considerably faster than verifying the tokens one by one.
+    ///
+    /// Returns a result for each of the tokens, in the same order. If the batch
+    /// verification fails, the signatures are checked individually to find the
+    /// tokens that failed.
+    ///
+    /// Note: Same as with [`VerifyableClaims::try_from_str()`], the claims
+    /// should be verified using the [`VerifyableClaims::verify_basic()`]
+    /// method afterwards.
+    fn verify_many(tokens: &[&str]) -> Vec<Result<Self, JwtError>>
+    where
+        Self: Sized,
+    {
+        let decoded = tokens
+            .iter()
+            .map(|data| {
+                let jwt = UnverifiedJwt::<Self>::decode(data)?;
+                let (key, signature) = jwt.signature_parts()?;
+
+                Ok((jwt, key, signature))
+            })
+            .collect::<Vec<Result<_, JwtError>>>();
+
+        let mut messages = Vec::with_capacity(decoded.len());
+        let mut signatures = Vec::with_capacity(decoded.len());
+        let mut keys = Vec::with_capacity(decoded.len());
+
+        for (jwt, key, signature) in decoded.iter().flatten() {
+            messages.push(jwt.message.as_bytes());
+            signatures.push(*signature);
+            keys.push(*key);
+        }
+
+        let batch_valid = messages.is_empty()
+            || ed25519_dalek::verify_batch(&messages, &signatures, &keys).is_ok();
+
+        decoded
+            .into_iter()
+            .map(|result| {
+                let (jwt, key, signature) = result?;
+
+                // The batch has failed, so we need to find out whether this particular
+                // signature is invalid.
+                if !batch_valid
+                    && key
+                        .verify_strict(jwt.message.as_bytes(), &signature)
+                        .is_err()
+                {
+                    return Err(JwtError::Signature);
+                }
+
+                Ok(jwt.claims)
+            })
+            .collect()
+    }
+
     /// Performs basic verification of the claims. This includes the following
     /// checks:
     /// - The token is not expired (with a configurable leeway). This is
@@ -237,10 +376,21 @@ mod test {
     use {
         crate::{
             auth::AuthToken,
-            domain::ClientId,
-            jwt::{JwtBasicClaims, JwtError, VerifyableClaims, JWT_VALIDATION_TIME_LEEWAY_SECS},
+            domain::{ClientId, DecodedClientId},
+            jwt::{
+                JwtBasicClaims,
+                JwtError,
+                JwtHeader,
+                VerifyableClaims,
+                JWT_VALIDATION_TIME_LEEWAY_SECS,
+            },
         },
-        ed25519_dalek::Keypair,
+        curve25519_dalek::{
+            constants::{ED25519_BASEPOINT_TABLE, EIGHT_TORSION},
+            scalar::Scalar,
+        },
+        ed25519_dalek::{ExpandedSecretKey, Keypair},
+        sha2::{Digest, Sha512},
         std::{collections::HashSet, time::Duration},
     };
 
@@ -361,4 +511,139 @@ mod test {
             Err(JwtError::InvalidAudience)
         ));
     }
+
+    #[test]
+    fn batch_token_validation() {
+        let keypair = Keypair::generate(&mut rand::thread_rng());
+
+        let tokens = (0..4)
+            .map(|idx| {
+                AuthToken::new(format!("test{idx}"))
+                    .as_jwt(&keypair)
+                    .map(String::from)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // All tokens are valid.
+        let refs = tokens.iter().map(String::as_str).collect::<Vec<_>>();
+        let results = JwtBasicClaims::verify_many(&refs);
+        assert_eq!(results.len(), tokens.len());
+
+        for (idx, result) in results.into_iter().enumerate() {
+            assert_eq!(result.unwrap().sub, format!("test{idx}"));
+        }
+
+        // Empty batch.
+        assert!(JwtBasicClaims::verify_many(&[]).is_empty());
+
+        // Signature from another token.
+        let (message, _) = tokens[1].rsplit_once('.').unwrap();
+        let (_, signature) = tokens[2].rsplit_once('.').unwrap();
+        let forged = format!("{message}.{signature}");
+
+        let refs = [
+            tokens[0].as_str(),
+            forged.as_str(),
+            "invalid",
+            tokens[3].as_str(),
+        ];
+        let results = JwtBasicClaims::verify_many(&refs);
+
+        assert_eq!(results[0].as_ref().unwrap().sub, "test0");
+        assert!(matches!(results[1], Err(JwtError::Signature)));
+        assert!(matches!(results[2], Err(JwtError::Format)));
+        assert_eq!(results[3].as_ref().unwrap().sub, "test3");
+    }
+
+    /// Signs the token message with a small-order component added to the
+    /// signature `R`, which passes the cofactored verification but not the
+    /// cofactorless one.
+    fn sign_with_torsion(keypair: &Keypair, message: &str) -> String {
+        let secret = ExpandedSecretKey::from(&keypair.secret).to_bytes();
+        let mut x = [0u8; 32];
+        x.copy_from_slice(&secret[..32]);
+        let x = Scalar::from_bits(x);
+
+        let r = Scalar::from_bytes_mod_order_wide(&Sha512::digest(message).into());
+        let big_r = (&r * &ED25519_BASEPOINT_TABLE + EIGHT_TORSION[1]).compress();
+
+        let k = Scalar::from_bytes_mod_order_wide(
+            &Sha512::new()
+                .chain_update(big_r.as_bytes())
+                .chain_update(keypair.public.as_bytes())
+                .chain_update(message)
+                .finalize()
+                .into(),
+        );
+        let s = r + k * x;
+
+        let mut signature = big_r.to_bytes().to_vec();
+        signature.extend_from_slice(s.as_bytes());
+
+        format!(
+            "{message}.{}",
+            data_encoding::BASE64URL_NOPAD.encode(&signature)
+        )
+    }
+
+    #[test]
+    fn batch_token_validation_consistent() {
+        let keypair = Keypair::generate(&mut rand::thread_rng());
+        let valid = String::from(AuthToken::new("valid").as_jwt(&keypair).unwrap());
+        let (message, _) = valid.rsplit_once('.').unwrap();
+        let torsion = sign_with_torsion(&keypair, message);
+        let forged = format!("{message}.{}", "A".repeat(86));
+
+        // The token is rejected next to both the valid and the invalid tokens.
+        let clean = JwtBasicClaims::verify_many(&[&torsion, &valid]);
+        assert!(matches!(clean[0], Err(JwtError::Signature)));
+        assert!(clean[1].is_ok());
+
+        let failing = JwtBasicClaims::verify_many(&[&torsion, &forged]);
+        assert!(matches!(failing[0], Err(JwtError::Signature)));
+        assert!(matches!(failing[1], Err(JwtError::Signature)));
+
+        // Same as with the single token verification.
+        assert!(matches!(
+            JwtBasicClaims::try_from_str(&torsion),
+            Err(JwtError::Signature)
+        ));
+
+        // The identity point as the key and the signature `R`, with a zero `S`,
+        // satisfies the verification equation for any message.
+        let mut identity = [0u8; 32];
+        identity[0] = 1;
+
+        let claims = JwtBasicClaims {
+            iss: DecodedClientId(identity).into(),
+            aud: "wss://relay.walletconnect.com".to_owned(),
+            sub: "weak".to_owned(),
+            iat: chrono::Utc::now().timestamp(),
+            exp: None,
+        };
+        let encoder = &data_encoding::BASE64URL_NOPAD;
+        let header = serde_json::to_string(&JwtHeader::default()).unwrap();
+        let claims = serde_json::to_string(&claims).unwrap();
+        let mut signature = identity.to_vec();
+        signature.extend_from_slice(&[0; 32]);
+        let weak = format!(
+            "{}.{}.{}",
+            encoder.encode(header.as_bytes()),
+            encoder.encode(claims.as_bytes()),
+            encoder.encode(&signature)
+        );
+
+        let clean = JwtBasicClaims::verify_many(&[&weak, &valid]);
+        assert!(matches!(clean[0], Err(JwtError::Signature)));
+        assert!(clean[1].is_ok());
+
+        let failing = JwtBasicClaims::verify_many(&[&weak, &forged]);
+        assert!(matches!(failing[0], Err(JwtError::Signature)));
+
+        assert!(matches!(
+            JwtBasicClaims::try_from_str(&weak),
+            Err(JwtError::Signature)
+        ));
+    }
 }
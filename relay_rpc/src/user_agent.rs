@@ -1,11 +1,13 @@
 //! Provides types and parsing of user agent strings.
 
+pub use policy::*;
 use {
     serde::{de, Deserialize, Serialize},
     std::{fmt::Display, str::FromStr},
     thiserror::Error as ThisError,
 };
 
+mod policy;
 #[cfg(test)]
 mod tests;
 
@@ -73,7 +75,7 @@ pub struct Protocol {
     pub version: u32,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum SdkLanguage {
     Js,
     Swift,
@@ -89,7 +91,7 @@ pub struct Sdk {
     pub version: String,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Environment {
     Browser,
     ReactNative,
@@ -105,10 +107,20 @@ pub struct Id {
     pub host: Option<String>,
 }
 
+/// Builder for the user agent of this SDK. See [`UserAgent::rust_sdk()`].
+#[derive(Debug, Clone)]
+pub struct UserAgentBuilder {
+    protocol: Protocol,
+    sdk: Sdk,
+    os: OsInfo,
+    id: Option<Id>,
+}
+
 const USER_AGENT_DELIMITER: char = '/';
 
 const PROTOCOL_DELIMITER: char = '-';
 const PROTOCOL_WALLETCONNECT: &str = "wc";
+const PROTOCOL_WALLETCONNECT_VERSION: u32 = 2;
 
 const SDK_DELIMITER: char = '-';
 const SDK_LANGUAGE_JS: &str = "js";
@@ -126,6 +138,83 @@ const ENV_IOS: &str = "ios";
 
 const OS_DELIMITER: char = '-';
 
+/// Parses the version leniently into a semantic version: the `v` prefix is
+/// allowed, and the missing minor and patch components default to zero (e.g.
+/// `12.4` is `12.4.0`). Components beyond the patch are ignored.
+pub fn parse_version(value: &str) -> Option<semver::Version> {
+    let value = value.strip_prefix('v').unwrap_or(value);
+    let (core, suffix) = value
+        .find(['-', '+'])
+        .map_or((value, ""), |idx| value.split_at(idx));
+
+    let mut components = [0u64; 3];
+    let mut parts = core.split('.');
+
+    for component in &mut components {
+        match parts.next() {
+            Some(part) => *component = part.parse().ok()?,
+            None => break,
+        }
+    }
+
+    // Versions like `10.0.19041.1` are still comparable by the first three
+    // components, but they must be numeric.
+    if !parts.all(|part| part.parse::<u64>().is_ok()) {
+        return None;
+    }
+
+    let [major, minor, patch] = components;
+
+    format!("{major}.{minor}.{patch}{suffix}").parse().ok()
+}
+
+impl Sdk {
+    /// Parses the SDK version. See [`parse_version()`].
+    pub fn semver(&self) -> Option<semver::Version> {
+        parse_version(&self.version)
+    }
+}
+
+impl OsInfo {
+    /// Parses the OS version. See [`parse_version()`].
+    pub fn semver(&self) -> Option<semver::Version> {
+        self.version.as_deref().and_then(parse_version)
+    }
+
+    /// Detects the OS family and version at runtime. The version is omitted
+    /// if it can't be determined.
+    ///
+    /// The detection may spawn a process (e.g. `lsb_release`), so it only runs
+    /// once, and the result is reused afterwards.
+    pub fn detect() -> Self {
+        use {once_cell::sync::Lazy, regex::Regex};
+
+        static VERSION: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\d+(\.\d+){0,2}").unwrap());
+
+        static DETECTED: Lazy<OsInfo> = Lazy::new(|| {
+            let version = match os_info::get().version() {
+                os_info::Version::Semantic(major, minor, patch) => {
+                    Some(format!("{major}.{minor}.{patch}"))
+                }
+
+                os_info::Version::Rolling(Some(version)) | os_info::Version::Custom(version) => {
+                    VERSION.find(version).map(|m| m.as_str().to_owned())
+                }
+
+                _ => None,
+            };
+
+            OsInfo {
+                os_family: std::env::consts::OS.replace(OS_DELIMITER, ""),
+                ua_family: None,
+                version,
+            }
+        });
+
+        DETECTED.clone()
+    }
+}
+
 impl FromStr for OsInfo {
     type Err = ParsingError;
 
@@ -369,6 +458,62 @@ impl Display for Id {
     }
 }
 
+impl UserAgent {
+    /// Creates a builder for the user agent of this SDK, filled with the
+    /// WalletConnect v2 protocol, the Rust SDK version from the crate metadata
+    /// and the OS detected at runtime.
+    pub fn rust_sdk() -> UserAgentBuilder {
+        UserAgentBuilder {
+            protocol: Protocol {
+                kind: ProtocolKind::WalletConnect,
+                version: PROTOCOL_WALLETCONNECT_VERSION,
+            },
+            sdk: Sdk {
+                language: SdkLanguage::Rust,
+                version: env!("CARGO_PKG_VERSION").to_owned(),
+            },
+            os: OsInfo::detect(),
+            id: None,
+        }
+    }
+}
+
+impl UserAgentBuilder {
+    /// Overrides the SDK version, e.g. with the version of the application
+    /// crate.
+    pub fn sdk_version(mut self, version: impl Into<String>) -> Self {
+        self.sdk.version = version.into();
+        self
+    }
+
+    /// Overrides the detected OS.
+    pub fn os(mut self, os: OsInfo) -> Self {
+        self.os = os;
+        self
+    }
+
+    /// Sets the environment and the host of the application.
+    pub fn id(mut self, id: impl Into<Option<Id>>) -> Self {
+        self.id = id.into();
+        self
+    }
+
+    pub fn build(self) -> UserAgent {
+        UserAgent::ValidUserAgent(ValidUserAgent {
+            protocol: self.protocol,
+            sdk: self.sdk,
+            os: self.os,
+            id: self.id,
+        })
+    }
+}
+
+impl From<UserAgentBuilder> for UserAgent {
+    fn from(builder: UserAgentBuilder) -> Self {
+        builder.build()
+    }
+}
+
 impl Display for UserAgent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -0,0 +1,266 @@
+use {
+    super::{Environment, SdkLanguage, UserAgent},
+    semver::Version,
+    std::{
+        collections::{HashMap, HashSet},
+        fmt::Display,
+    },
+};
+
+/// Outcome of the [`UserAgentPolicy`] evaluation, ordered by severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Verdict {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Reason for a [`Verdict`] other than [`Verdict::Allow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    /// The user agent doesn't follow the WalletConnect user agent spec.
+    UnknownUserAgent,
+
+    /// The SDK version is not a valid version, so it can't be checked against
+    /// the configured minimum.
+    InvalidSdkVersion {
+        language: SdkLanguage,
+        version: String,
+    },
+
+    /// The SDK version is below the minimum version.
+    OutdatedSdk {
+        language: SdkLanguage,
+        version: Version,
+        min_version: Version,
+    },
+
+    /// The SDK version is below the recommended version.
+    DeprecatedSdk {
+        language: SdkLanguage,
+        version: Version,
+        recommended_version: Version,
+    },
+
+    /// The OS version is below the minimum version.
+    OutdatedOs {
+        os_family: String,
+        version: Version,
+        min_version: Version,
+    },
+
+    /// The environment is blocked.
+    BlockedEnvironment(Environment),
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownUserAgent => f.write_str("unknown user agent"),
+
+            Self::InvalidSdkVersion { language, version } => {
+                write!(f, "invalid {language} sdk version: {version}")
+            }
+
+            Self::OutdatedSdk {
+                language,
+                version,
+                min_version,
+            } => write!(
+                f,
+                "{language} sdk {version} is outdated, minimum version is {min_version}"
+            ),
+
+            Self::DeprecatedSdk {
+                language,
+                version,
+                recommended_version,
+            } => write!(
+                f,
+                "{language} sdk {version} is deprecated, recommended version is \
+                 {recommended_version}"
+            ),
+
+            Self::OutdatedOs {
+                os_family,
+                version,
+                min_version,
+            } => write!(
+                f,
+                "{os_family} {version} is outdated, minimum version is {min_version}"
+            ),
+
+            Self::BlockedEnvironment(environment) => {
+                write!(f, "environment is blocked: {environment}")
+            }
+        }
+    }
+}
+
+/// Result of the [`UserAgentPolicy`] evaluation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    /// The most severe verdict of all the reasons, or [`Verdict::Allow`] if
+    /// there are none.
+    pub verdict: Verdict,
+
+    /// Reasons for the verdict.
+    pub reasons: Vec<Reason>,
+}
+
+impl Evaluation {
+    fn add(&mut self, verdict: Verdict, reason: Reason) {
+        if verdict > Verdict::Allow {
+            self.verdict = self.verdict.max(verdict);
+            self.reasons.push(reason);
+        }
+    }
+
+    pub fn is_allowed(&self) -> bool {
+        self.verdict != Verdict::Deny
+    }
+}
+
+/// Policy for gating clients by their user agent, e.g. to reject or throttle
+/// outdated SDKs.
+///
+/// The default policy allows everything.
+#[derive(Debug, Clone)]
+pub struct UserAgentPolicy {
+    min_sdk_versions: HashMap<SdkLanguage, Version>,
+    recommended_sdk_versions: HashMap<SdkLanguage, Version>,
+    min_os_versions: HashMap<String, Version>,
+    blocked_environments: HashSet<Environment>,
+    unknown_user_agents: Verdict,
+    invalid_versions: Verdict,
+}
+
+impl Default for UserAgentPolicy {
+    fn default() -> Self {
+        Self {
+            min_sdk_versions: HashMap::new(),
+            recommended_sdk_versions: HashMap::new(),
+            min_os_versions: HashMap::new(),
+            blocked_environments: HashSet::new(),
+            unknown_user_agents: Verdict::Allow,
+            invalid_versions: Verdict::Warn,
+        }
+    }
+}
+
+impl UserAgentPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Denies the SDKs of the language below the version.
+    pub fn with_min_sdk_version(mut self, language: SdkLanguage, version: Version) -> Self {
+        self.min_sdk_versions.insert(language, version);
+        self
+    }
+
+    /// Warns about the SDKs of the language below the version.
+    pub fn with_recommended_sdk_version(mut self, language: SdkLanguage, version: Version) -> Self {
+        self.recommended_sdk_versions.insert(language, version);
+        self
+    }
+
+    /// Denies the OS family (e.g. `ios`) below the version. OS versions that
+    /// can't be parsed are ignored, since they're often omitted.
+    pub fn with_min_os_version(mut self, os_family: impl Into<String>, version: Version) -> Self {
+        self.min_os_versions
+            .insert(os_family.into().to_lowercase(), version);
+        self
+    }
+
+    pub fn with_blocked_environment(mut self, environment: Environment) -> Self {
+        self.blocked_environments.insert(environment);
+        self
+    }
+
+    /// Verdict for the user agents that don't follow the WalletConnect user
+    /// agent spec. Defaults to [`Verdict::Allow`].
+    pub fn with_unknown_user_agents(mut self, verdict: Verdict) -> Self {
+        self.unknown_user_agents = verdict;
+        self
+    }
+
+    /// Verdict for the SDK versions that can't be parsed while there's a
+    /// minimum or recommended version for the language. Defaults to
+    /// [`Verdict::Warn`].
+    pub fn with_invalid_versions(mut self, verdict: Verdict) -> Self {
+        self.invalid_versions = verdict;
+        self
+    }
+
+    pub fn evaluate(&self, user_agent: &UserAgent) -> Evaluation {
+        let mut evaluation = Evaluation {
+            verdict: Verdict::Allow,
+            reasons: Vec::new(),
+        };
+
+        let ua = match user_agent {
+            UserAgent::ValidUserAgent(ua) => ua,
+
+            UserAgent::Unknown(_) => {
+                evaluation.add(self.unknown_user_agents, Reason::UnknownUserAgent);
+                return evaluation;
+            }
+        };
+
+        let language = &ua.sdk.language;
+        let min_version = self.min_sdk_versions.get(language);
+        let recommended_version = self.recommended_sdk_versions.get(language);
+
+        if min_version.is_some() || recommended_version.is_some() {
+            match ua.sdk.semver() {
+                Some(version) => {
+                    if let Some(min_version) = min_version.filter(|min| version < **min) {
+                        evaluation.add(Verdict::Deny, Reason::OutdatedSdk {
+                            language: language.clone(),
+                            version,
+                            min_version: min_version.clone(),
+                        });
+                    } else if let Some(recommended_version) =
+                        recommended_version.filter(|recommended| version < **recommended)
+                    {
+                        evaluation.add(Verdict::Warn, Reason::DeprecatedSdk {
+                            language: language.clone(),
+                            version,
+                            recommended_version: recommended_version.clone(),
+                        });
+                    }
+                }
+
+                None => evaluation.add(self.invalid_versions, Reason::InvalidSdkVersion {
+                    language: language.clone(),
+                    version: ua.sdk.version.clone(),
+                }),
+            }
+        }
+
+        if let (Some(min_version), Some(version)) = (
+            self.min_os_versions.get(&ua.os.os_family.to_lowercase()),
+            ua.os.semver(),
+        ) {
+            if version < *min_version {
+                evaluation.add(Verdict::Deny, Reason::OutdatedOs {
+                    os_family: ua.os.os_family.clone(),
+                    version,
+                    min_version: min_version.clone(),
+                });
+            }
+        }
+
+        if let Some(id) = &ua.id {
+            if self.blocked_environments.contains(&id.environment) {
+                evaluation.add(
+                    Verdict::Deny,
+                    Reason::BlockedEnvironment(id.environment.clone()),
+                );
+            }
+        }
+
+        evaluation
+    }
+}
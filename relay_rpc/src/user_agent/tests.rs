@@ -1,14 +1,21 @@
-use super::{
-    Environment,
-    Id,
-    OsInfo,
-    ParsingError,
-    Protocol,
-    ProtocolKind,
-    Sdk,
-    SdkLanguage,
-    UserAgent,
-    ValidUserAgent,
+use {
+    super::{
+        parse_version,
+        Environment,
+        Id,
+        OsInfo,
+        ParsingError,
+        Protocol,
+        ProtocolKind,
+        Reason,
+        Sdk,
+        SdkLanguage,
+        UserAgent,
+        UserAgentPolicy,
+        ValidUserAgent,
+        Verdict,
+    },
+    semver::Version,
 };
 
 #[test]
@@ -226,3 +233,164 @@ fn parse_os() {
 
     assert_eq!("".parse::<OsInfo>(), Err(ParsingError::Os));
 }
+
+#[test]
+fn rust_sdk_ua() {
+    let ua = UserAgent::rust_sdk().build();
+
+    let UserAgent::ValidUserAgent(valid) = &ua else {
+        panic!("invalid user agent: {ua}");
+    };
+
+    assert_eq!(valid.protocol, Protocol {
+        kind: ProtocolKind::WalletConnect,
+        version: 2
+    });
+    assert_eq!(valid.sdk, Sdk {
+        language: SdkLanguage::Rust,
+        version: env!("CARGO_PKG_VERSION").to_owned()
+    });
+    assert_eq!(valid.os.os_family, std::env::consts::OS);
+    assert_eq!(valid.id, None);
+
+    // The detected OS survives a round trip.
+    assert_eq!(ua.to_string().parse::<UserAgent>().unwrap(), ua);
+
+    let ua = UserAgent::rust_sdk()
+        .sdk_version("1.2.3")
+        .os("linux-6.1.0".parse().unwrap())
+        .id(Id {
+            environment: Environment::Unknown("server".to_owned()),
+            host: Some("app.example.org".to_owned()),
+        })
+        .build();
+
+    assert_eq!(
+        ua.to_string(),
+        "wc-2/rust-1.2.3/linux-6.1.0/server:app.example.org"
+    );
+}
+
+#[test]
+fn parse_versions() {
+    let fixtures = [
+        ("2.0.0-rc.1", Some("2.0.0-rc.1")),
+        ("v1.2.3", Some("1.2.3")),
+        ("12.4", Some("12.4.0")),
+        ("9", Some("9.0.0")),
+        ("10.0.19041.1", Some("10.0.19041")),
+        ("1.2.3+build.5", Some("1.2.3+build.5")),
+        ("", None),
+        ("1.x", None),
+        ("latest", None),
+    ];
+
+    for (value, expected) in fixtures {
+        assert_eq!(
+            parse_version(value),
+            expected.map(|expected| Version::parse(expected).unwrap()),
+            "{value}"
+        );
+    }
+
+    let sdk: Sdk = "js-2.0.0-rc.1".parse().unwrap();
+    assert!(sdk.semver().unwrap() < Version::new(2, 0, 0));
+
+    let os: OsInfo = "ios-12.4".parse().unwrap();
+    assert_eq!(os.semver(), Some(Version::new(12, 4, 0)));
+}
+
+#[test]
+fn user_agent_policy() {
+    let policy = UserAgentPolicy::new()
+        .with_min_sdk_version(SdkLanguage::Js, Version::new(2, 0, 0))
+        .with_recommended_sdk_version(SdkLanguage::Js, Version::new(2, 5, 0))
+        .with_min_os_version("iOS", Version::new(13, 0, 0))
+        .with_blocked_environment(Environment::ReactNative)
+        .with_unknown_user_agents(Verdict::Deny);
+
+    let evaluate = |ua: &str| policy.evaluate(&ua.parse().unwrap());
+
+    let evaluation = evaluate("wc-2/js-2.6.0/android-9/browser:app.example.org");
+    assert_eq!(evaluation.verdict, Verdict::Allow);
+    assert!(evaluation.reasons.is_empty());
+
+    // Languages without a minimum version aren't checked.
+    assert_eq!(
+        evaluate("wc-2/swift-1.0.0/ios-16.1").verdict,
+        Verdict::Allow
+    );
+
+    let evaluation = evaluate("wc-2/js-2.1.0/android-9");
+    assert_eq!(evaluation.verdict, Verdict::Warn);
+    assert!(evaluation.is_allowed());
+    assert_eq!(evaluation.reasons, [Reason::DeprecatedSdk {
+        language: SdkLanguage::Js,
+        version: Version::new(2, 1, 0),
+        recommended_version: Version::new(2, 5, 0),
+    }]);
+
+    let evaluation = evaluate("wc-2/js-2.0.0-rc.1/ios-12.4/react-native");
+    assert_eq!(evaluation.verdict, Verdict::Deny);
+    assert!(!evaluation.is_allowed());
+    assert_eq!(evaluation.reasons, [
+        Reason::OutdatedSdk {
+            language: SdkLanguage::Js,
+            version: Version::parse("2.0.0-rc.1").unwrap(),
+            min_version: Version::new(2, 0, 0),
+        },
+        Reason::OutdatedOs {
+            os_family: "ios".to_owned(),
+            version: Version::new(12, 4, 0),
+            min_version: Version::new(13, 0, 0),
+        },
+        Reason::BlockedEnvironment(Environment::ReactNative),
+    ]);
+    assert_eq!(
+        evaluation.reasons[0].to_string(),
+        "js sdk 2.0.0-rc.1 is outdated, minimum version is 2.0.0"
+    );
+
+    // The OS family is matched regardless of the case, e.g. in the user agents
+    // built in code.
+    let user_agent = UserAgent::ValidUserAgent(ValidUserAgent {
+        protocol: Protocol {
+            kind: ProtocolKind::WalletConnect,
+            version: 2,
+        },
+        sdk: Sdk {
+            language: SdkLanguage::Rust,
+            version: "1.0.0".to_owned(),
+        },
+        os: OsInfo {
+            os_family: "iOS".to_owned(),
+            ua_family: None,
+            version: Some("12.4".to_owned()),
+        },
+        id: None,
+    });
+    assert_eq!(policy.evaluate(&user_agent).reasons, [Reason::OutdatedOs {
+        os_family: "iOS".to_owned(),
+        version: Version::new(12, 4, 0),
+        min_version: Version::new(13, 0, 0),
+    }]);
+
+    let evaluation = evaluate("wc-2/js-latest/android-9");
+    assert_eq!(evaluation.verdict, Verdict::Warn);
+    assert_eq!(evaluation.reasons, [Reason::InvalidSdkVersion {
+        language: SdkLanguage::Js,
+        version: "latest".to_owned(),
+    }]);
+
+    let evaluation = evaluate("curl/8.0.1");
+    assert_eq!(evaluation.verdict, Verdict::Deny);
+    assert_eq!(evaluation.reasons, [Reason::UnknownUserAgent]);
+
+    // The default policy allows everything.
+    assert_eq!(
+        UserAgentPolicy::default()
+            .evaluate(&"curl/8.0.1".parse().unwrap())
+            .verdict,
+        Verdict::Allow
+    );
+}